@@ -0,0 +1,85 @@
+/// Transfer function between linear intensities and encoded values, both in `[0, 1]`.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Encoding {
+    Linear,
+    /// Piecewise sRGB curve.
+    #[default]
+    Srgb,
+    /// Power law `x ^ (1 / gamma)`.
+    Gamma(f64),
+}
+
+impl Encoding {
+    pub fn encode(self, x: f64) -> f64 {
+        let x = x.clamp(0., 1.);
+        match self {
+            Encoding::Linear => x,
+            Encoding::Srgb => if x <= 0.003_130_8 {
+                12.92 * x
+            } else {
+                1.055 * x.powf(1. / 2.4) - 0.055
+            },
+            Encoding::Gamma(gamma) => x.powf(1. / gamma),
+        }
+    }
+
+    pub fn decode(self, x: f64) -> f64 {
+        let x = x.clamp(0., 1.);
+        match self {
+            Encoding::Linear => x,
+            Encoding::Srgb => if x <= 0.040_45 {
+                x / 12.92
+            } else {
+                ((x + 0.055) / 1.055).powf(2.4)
+            },
+            Encoding::Gamma(gamma) => x.powf(gamma),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(a: f64, b: f64) {
+        assert!((a - b).abs() < 1e-9, "{} != {}", a, b);
+    }
+
+    #[test]
+    fn srgb_round_trip() {
+        for i in 0..=100 {
+            let x = i as f64 / 100.;
+            assert_close(Encoding::Srgb.decode(Encoding::Srgb.encode(x)), x);
+        }
+        assert_close(Encoding::Srgb.encode(0.), 0.);
+        assert_close(Encoding::Srgb.encode(1.), 1.);
+        assert_close(Encoding::Srgb.encode(0.18), 0.461_356_129_500_441_5);
+    }
+
+    #[test]
+    fn srgb_segments_meet() {
+        let edge = 0.003_130_8;
+        let linear = Encoding::Srgb.encode(edge);
+        assert_close(linear, 12.92 * edge);
+        // The power segment continues the linear one without a visible step.
+        assert!((Encoding::Srgb.encode(edge + 1e-12) - linear).abs() < 1e-6);
+        assert_close(Encoding::Srgb.decode(linear), edge);
+        assert_close(Encoding::Srgb.decode(0.040_45), 0.040_45 / 12.92);
+    }
+
+    #[test]
+    fn gamma() {
+        assert_close(Encoding::Gamma(2.).encode(0.25), 0.5);
+        assert_close(Encoding::Gamma(2.).decode(0.5), 0.25);
+        assert_close(Encoding::Gamma(2.2).decode(Encoding::Gamma(2.2).encode(0.3)), 0.3);
+        assert_close(Encoding::Linear.encode(0.3), 0.3);
+    }
+
+    #[test]
+    fn clamps() {
+        for encoding in [Encoding::Linear, Encoding::Srgb, Encoding::Gamma(2.2)] {
+            assert_close(encoding.encode(-1.), 0.);
+            assert_close(encoding.encode(2.), 1.);
+        }
+    }
+}
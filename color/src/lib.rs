@@ -5,6 +5,12 @@ use std::ops::{AddAssign, Mul};
 
 use num_traits::{Bounded, CheckedAdd, Float, Num, ToPrimitive, Unsigned, Zero};
 
+pub use encoding::Encoding;
+pub use tone_map::ToneMap;
+
+mod encoding;
+mod tone_map;
+
 #[derive(Clone, Copy, Debug)]
 pub struct Color<T: Num> {
     pub r: T,
@@ -38,6 +44,30 @@ impl<T: Bounded + Unsigned> Color<T> {
     }
 }
 
+impl Color<f64> {
+    /// Applies `encoding` to linear channels in `[0, 1]`.
+    pub fn encode(self, encoding: Encoding) -> Self {
+        Color {
+            r: encoding.encode(self.r),
+            g: encoding.encode(self.g),
+            b: encoding.encode(self.b),
+        }
+    }
+
+    pub fn decode(self, encoding: Encoding) -> Self {
+        Color {
+            r: encoding.decode(self.r),
+            g: encoding.decode(self.g),
+            b: encoding.decode(self.b),
+        }
+    }
+
+    /// Relative luminance with Rec. 709 primaries.
+    pub fn luminance(self) -> f64 {
+        0.2126 * self.r + 0.7152 * self.g + 0.0722 * self.b
+    }
+}
+
 impl<T> ops::Add<Color<T>> for Color<T>
     where T: Num + Bounded + CheckedAdd
 {
@@ -94,18 +124,25 @@ macro_rules! color_impls {
             }
         }
 
-        impl From<Color<$float>> for Color<u8> {
+        int_conversions!($float, u8);
+        int_conversions!($float, u16);
+    }
+}
+
+macro_rules! int_conversions {
+    ( $float:ty, $int:ty ) => {
+        impl From<Color<$float>> for Color<$int> {
             fn from(Color { r, g, b }: Color<$float>) -> Self {
                 Color {
-                    r: r.round() as u8,
-                    g: g.round() as u8,
-                    b: b.round() as u8,
+                    r: r.round() as $int,
+                    g: g.round() as $int,
+                    b: b.round() as $int,
                 }
             }
         }
 
-        impl From<Color<u8>> for Color<$float> {
-            fn from(Color { r, g, b }: Color<u8>) -> Self {
+        impl From<Color<$int>> for Color<$float> {
+            fn from(Color { r, g, b }: Color<$int>) -> Self {
                 Color {
                     r: r as $float,
                     g: g as $float,
@@ -118,3 +155,23 @@ macro_rules! color_impls {
 
 color_impls!(f32);
 color_impls!(f64);
+
+impl From<Color<f32>> for Color<f64> {
+    fn from(Color { r, g, b }: Color<f32>) -> Self {
+        Color {
+            r: r as f64,
+            g: g as f64,
+            b: b as f64,
+        }
+    }
+}
+
+impl From<Color<f64>> for Color<f32> {
+    fn from(Color { r, g, b }: Color<f64>) -> Self {
+        Color {
+            r: r as f32,
+            g: g as f32,
+            b: b as f32,
+        }
+    }
+}
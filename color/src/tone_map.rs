@@ -0,0 +1,109 @@
+use crate::Color;
+
+/// Operator compressing linear radiance into `[0, 1]` before encoding.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum ToneMap {
+    /// Values above `1` saturate.
+    #[default]
+    Clamp,
+    /// `L / (1 + L)` of the luminance, keeps hue.
+    Reinhard,
+    /// Reinhard operator mapping luminance `white` and above to `1`.
+    ExtendedReinhard { white: f64 },
+    /// Narkowicz fit of the ACES filmic curve.
+    Aces,
+    /// Filmic curve of Uncharted 2 by John Hable.
+    Hable,
+}
+
+impl ToneMap {
+    pub fn apply(self, c: Color<f64>) -> Color<f64> {
+        match self {
+            ToneMap::Clamp => channels(c, |x| x.clamp(0., 1.)),
+            ToneMap::Reinhard => scale_luminance(c, |l| l / (1. + l)),
+            ToneMap::ExtendedReinhard { white } => {
+                scale_luminance(c, |l| l * (1. + l / (white * white)) / (1. + l))
+            }
+            ToneMap::Aces => channels(c, |x| {
+                let x = x.max(0.);
+                (x * (2.51 * x + 0.03) / (x * (2.43 * x + 0.59) + 0.14)).clamp(0., 1.)
+            }),
+            ToneMap::Hable => {
+                const EXPOSURE_BIAS: f64 = 2.;
+                const WHITE: f64 = 11.2;
+                let scale = 1. / hable(WHITE);
+                channels(c, |x| (hable(EXPOSURE_BIAS * x.max(0.)) * scale).clamp(0., 1.))
+            }
+        }
+    }
+}
+
+fn channels(c: Color<f64>, f: impl Fn(f64) -> f64) -> Color<f64> {
+    Color { r: f(c.r), g: f(c.g), b: f(c.b) }
+}
+
+/// Scales channels by the ratio of mapped and original luminance, the result is clamped.
+fn scale_luminance(c: Color<f64>, f: impl Fn(f64) -> f64) -> Color<f64> {
+    let l = c.luminance();
+    if l <= 0. {
+        return Color { r: 0., g: 0., b: 0. };
+    }
+    let k = f(l) / l;
+    channels(c, |x| (k * x).clamp(0., 1.))
+}
+
+fn hable(x: f64) -> f64 {
+    const A: f64 = 0.15;
+    const B: f64 = 0.50;
+    const C: f64 = 0.10;
+    const D: f64 = 0.20;
+    const E: f64 = 0.02;
+    const F: f64 = 0.30;
+    (x * (A * x + C * B) + D * E) / (x * (A * x + B) + D * F) - E / F
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gray(x: f64) -> Color<f64> {
+        Color { r: x, g: x, b: x }
+    }
+
+    const CURVES: [ToneMap; 4] = [
+        ToneMap::Reinhard,
+        ToneMap::ExtendedReinhard { white: 4. },
+        ToneMap::Aces,
+        ToneMap::Hable,
+    ];
+
+    #[test]
+    fn monotonic() {
+        for tone_map in CURVES {
+            let mut prev = tone_map.apply(gray(0.)).r;
+            assert!(prev.abs() < 1e-9, "{:?} maps black to {}", tone_map, prev);
+            for i in 1..=1000 {
+                let y = tone_map.apply(gray(i as f64 / 50.)).r;
+                assert!(y >= prev && y <= 1., "{:?} at {}", tone_map, i);
+                prev = y;
+            }
+        }
+    }
+
+    #[test]
+    fn white_points() {
+        assert!((ToneMap::Reinhard.apply(gray(1.)).r - 0.5).abs() < 1e-9);
+        let extended = ToneMap::ExtendedReinhard { white: 4. };
+        assert!((extended.apply(gray(4.)).r - 1.).abs() < 1e-9);
+        assert!(extended.apply(gray(3.9)).r < 1.);
+        // The exposure bias of 2 brings 5.6 to the white of the curve.
+        assert!((ToneMap::Hable.apply(gray(5.6)).r - 1.).abs() < 1e-9);
+        assert_eq!(ToneMap::Clamp.apply(Color { r: -1., g: 0.5, b: 2. }).g, 0.5);
+    }
+
+    #[test]
+    fn reinhard_keeps_hue() {
+        let c = ToneMap::Reinhard.apply(Color { r: 0.4, g: 0.2, b: 0.1 });
+        assert!((c.r / c.g - 2.).abs() < 1e-9 && (c.g / c.b - 2.).abs() < 1e-9);
+    }
+}
@@ -0,0 +1,334 @@
+use std::{fs, io};
+use std::io::Write;
+use std::path::Path;
+
+use flate2::Compression;
+use flate2::write::ZlibEncoder;
+use half::f16;
+
+use crate::{Error, Image};
+
+#[derive(Clone, Copy, Debug)]
+pub enum ExrCompression {
+    None,
+    /// Run-length encoding, one scanline per block.
+    Rle,
+    /// Zlib, one scanline per block.
+    Zips,
+    /// Zlib, 16 scanlines per block.
+    Zip,
+}
+
+impl ExrCompression {
+    fn code(self) -> u8 {
+        match self {
+            ExrCompression::None => 0,
+            ExrCompression::Rle => 1,
+            ExrCompression::Zips => 2,
+            ExrCompression::Zip => 3,
+        }
+    }
+
+    fn lines_per_block(self) -> usize {
+        match self {
+            ExrCompression::Zip => 16,
+            _ => 1,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum ExrPixelType {
+    Half,
+    Float,
+}
+
+impl ExrPixelType {
+    fn code(self) -> i32 {
+        match self {
+            ExrPixelType::Half => 1,
+            ExrPixelType::Float => 2,
+        }
+    }
+}
+
+/// Scanline OpenEXR file with any number of named channels.
+/// Layers are groups of channels sharing the `layer.` name prefix.
+pub struct ExrWriter {
+    width: usize,
+    height: usize,
+    compression: ExrCompression,
+    pixel_type: ExrPixelType,
+    channels: Vec<(String, Vec<f32>)>,
+}
+
+impl ExrWriter {
+    pub fn new(width: usize, height: usize) -> Self {
+        ExrWriter {
+            width,
+            height,
+            compression: ExrCompression::Zip,
+            pixel_type: ExrPixelType::Half,
+            channels: vec![],
+        }
+    }
+
+    pub fn compression(mut self, compression: ExrCompression) -> Self {
+        self.compression = compression;
+        self
+    }
+
+    pub fn pixel_type(mut self, pixel_type: ExrPixelType) -> Self {
+        self.pixel_type = pixel_type;
+        self
+    }
+
+    /// Adds `R`, `G` and `B` channels of the layer, empty name stands for the default layer.
+    pub fn rgb_layer(self, name: &str, image: &Image<f32>) -> Self {
+        assert_eq!((image.w(), image.h()), (self.width, self.height), "Layer size mismatch");
+        let prefix = if name.is_empty() { String::new() } else { format!("{}.", name) };
+        let values = |f: fn(&color::Color<f32>) -> f32| {
+            image.rows().flat_map(|row| row.iter().map(f)).collect()
+        };
+        self.channel(&format!("{}R", prefix), values(|c| c.r))
+            .channel(&format!("{}G", prefix), values(|c| c.g))
+            .channel(&format!("{}B", prefix), values(|c| c.b))
+    }
+
+    /// Adds a single channel with values in row-major order.
+    pub fn channel(mut self, name: &str, values: Vec<f32>) -> Self {
+        assert_eq!(values.len(), self.width * self.height, "Channel size mismatch");
+        self.channels.push((name.to_string(), values));
+        self
+    }
+
+    pub fn write(&self, path: &Path) -> Result<(), Error> {
+        let file = fs::File::create(path)?;
+        let mut writer = io::BufWriter::new(file);
+        self.write_to(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn write_to(&self, w: &mut impl Write) -> io::Result<()> {
+        let mut channels: Vec<_> = self.channels.iter().collect();
+        channels.sort_by(|a, b| a.0.cmp(&b.0));
+
+        let header = self.header(&channels);
+        let blocks: Vec<_> = (0..self.height)
+            .step_by(self.compression.lines_per_block())
+            .map(|y| (y, self.block(&channels, y)))
+            .collect();
+
+        let long_names = channels.iter().any(|(name, _)| name.len() > 31);
+        w.write_all(&[0x76, 0x2f, 0x31, 0x01])?;
+        w.write_all(&(2u32 | if long_names { 0x400 } else { 0 }).to_le_bytes())?;
+        w.write_all(&header)?;
+        let mut offset = (8 + header.len() + 8 * blocks.len()) as u64;
+        for (_, data) in &blocks {
+            w.write_all(&offset.to_le_bytes())?;
+            offset += 8 + data.len() as u64;
+        }
+        for (y, data) in &blocks {
+            w.write_all(&(*y as i32).to_le_bytes())?;
+            w.write_all(&(data.len() as i32).to_le_bytes())?;
+            w.write_all(data)?;
+        }
+        Ok(())
+    }
+
+    fn header(&self, channels: &[&(String, Vec<f32>)]) -> Vec<u8> {
+        let mut chlist = vec![];
+        for (name, _) in channels {
+            chlist.extend_from_slice(name.as_bytes());
+            chlist.push(0);
+            chlist.extend_from_slice(&self.pixel_type.code().to_le_bytes());
+            chlist.extend_from_slice(&[0, 0, 0, 0]); // pLinear and reserved
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+            chlist.extend_from_slice(&1i32.to_le_bytes());
+        }
+        chlist.push(0);
+
+        let mut window = vec![];
+        for x in [0, 0, self.width as i32 - 1, self.height as i32 - 1].iter() {
+            window.extend_from_slice(&x.to_le_bytes());
+        }
+
+        let mut header = vec![];
+        let mut attribute = |name: &str, kind: &str, value: &[u8]| {
+            header.extend_from_slice(name.as_bytes());
+            header.push(0);
+            header.extend_from_slice(kind.as_bytes());
+            header.push(0);
+            header.extend_from_slice(&(value.len() as i32).to_le_bytes());
+            header.extend_from_slice(value);
+        };
+        attribute("channels", "chlist", &chlist);
+        attribute("compression", "compression", &[self.compression.code()]);
+        attribute("dataWindow", "box2i", &window);
+        attribute("displayWindow", "box2i", &window);
+        attribute("lineOrder", "lineOrder", &[0]);
+        attribute("pixelAspectRatio", "float", &1f32.to_le_bytes());
+        attribute("screenWindowCenter", "v2f", &[0; 8]);
+        attribute("screenWindowWidth", "float", &1f32.to_le_bytes());
+        header.push(0);
+        header
+    }
+
+    /// Compressed block of scanlines starting at `y`.
+    fn block(&self, channels: &[&(String, Vec<f32>)], y: usize) -> Vec<u8> {
+        let end = (y + self.compression.lines_per_block()).min(self.height);
+        let mut raw = vec![];
+        for line in y..end {
+            for (_, values) in channels {
+                for v in &values[line * self.width..(line + 1) * self.width] {
+                    match self.pixel_type {
+                        ExrPixelType::Half => raw.extend_from_slice(&f16::from_f32(*v).to_le_bytes()),
+                        ExrPixelType::Float => raw.extend_from_slice(&v.to_le_bytes()),
+                    }
+                }
+            }
+        }
+        let compressed = match self.compression {
+            ExrCompression::None => return raw,
+            ExrCompression::Rle => rle(&predict(&raw)),
+            ExrCompression::Zips | ExrCompression::Zip => zip(&predict(&raw)),
+        };
+        // Readers treat blocks not smaller than raw data as uncompressed.
+        if compressed.len() < raw.len() { compressed } else { raw }
+    }
+}
+
+/// Splits even and odd bytes into halves and replaces bytes with deltas,
+/// the preprocessing shared by RLE and ZIP compression.
+fn predict(raw: &[u8]) -> Vec<u8> {
+    let half = raw.len().div_ceil(2);
+    let mut res = vec![0; raw.len()];
+    for (i, b) in raw.iter().enumerate() {
+        let j = if i % 2 == 0 { i / 2 } else { half + i / 2 };
+        res[j] = *b;
+    }
+    let mut prev = res.first().copied().unwrap_or(0);
+    for b in res.iter_mut().skip(1) {
+        let cur = *b;
+        *b = cur.wrapping_sub(prev).wrapping_add(128);
+        prev = cur;
+    }
+    res
+}
+
+fn zip(data: &[u8]) -> Vec<u8> {
+    let mut encoder = ZlibEncoder::new(vec![], Compression::default());
+    encoder.write_all(data).unwrap();
+    encoder.finish().unwrap()
+}
+
+/// Runs of at least 3 equal bytes are stored as `(count - 1, byte)`,
+/// other bytes as `(-count, bytes...)`.
+fn rle(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN: usize = 3;
+    const MAX_RUN: usize = 127;
+    const MAX_LITERAL: usize = 127;
+    let mut res = vec![];
+    let mut start = 0;
+    while start < data.len() {
+        let mut end = start + 1;
+        while end < data.len() && data[end] == data[start] && end - start <= MAX_RUN {
+            end += 1;
+        }
+        if end - start >= MIN_RUN {
+            res.push((end - start - 1) as u8);
+            res.push(data[start]);
+        } else {
+            while end < data.len()
+                && end - start < MAX_LITERAL
+                && !(end + 2 < data.len() && data[end] == data[end + 1] && data[end] == data[end + 2]) {
+                end += 1;
+            }
+            res.push((-((end - start) as i32)) as u8);
+            res.extend_from_slice(&data[start..end]);
+        }
+        start = end;
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use ::exr::prelude::{AnyChannels, FlatSamples, Image as ExrImage, Layer, read, ReadChannels, ReadLayers};
+
+    use super::*;
+
+    const WIDTH: usize = 7;
+    // More than a single ZIP block.
+    const HEIGHT: usize = 20;
+
+    /// Flat areas for RLE runs next to noisy ones, all values are exact halves.
+    fn beauty() -> Image<f32> {
+        let rows: Vec<Vec<_>> = (0..HEIGHT)
+            .map(|y| (0..WIDTH)
+                .map(|x| if y < HEIGHT / 2 {
+                    color::Color { r: 0.5, g: 0.25, b: 2. }
+                } else {
+                    let v = ((x * 31 + y * 17) % 23) as f32 / 8.;
+                    color::Color { r: v, g: -v, b: v * 4. }
+                })
+                .collect())
+            .collect();
+        Image::from(rows)
+    }
+
+    fn depth() -> Vec<f32> {
+        (0..WIDTH * HEIGHT).map(|i| i as f32 * 0.125).collect()
+    }
+
+    fn read_back(writer: ExrWriter) -> ExrImage<Layer<AnyChannels<FlatSamples>>> {
+        let mut bytes = vec![];
+        writer.write_to(&mut bytes).unwrap();
+        read()
+            .no_deep_data()
+            .largest_resolution_level()
+            .all_channels()
+            .first_valid_layer()
+            .all_attributes()
+            .from_buffered(Cursor::new(bytes))
+            .unwrap()
+    }
+
+    fn channel(image: &ExrImage<Layer<AnyChannels<FlatSamples>>>, name: &str) -> Vec<f32> {
+        let channel = image.layer_data.channel_data.list.iter()
+            .find(|c| c.name.eq(name))
+            .unwrap_or_else(|| panic!("no channel {}", name));
+        channel.sample_data.values_as_f32().collect()
+    }
+
+    #[test]
+    fn read_back_every_compression() {
+        let compressions = [ExrCompression::None, ExrCompression::Rle, ExrCompression::Zips, ExrCompression::Zip];
+        for pixel_type in [ExrPixelType::Half, ExrPixelType::Float] {
+            for compression in compressions {
+                let image = read_back(ExrWriter::new(WIDTH, HEIGHT)
+                    .pixel_type(pixel_type)
+                    .compression(compression)
+                    .rgb_layer("", &beauty())
+                    .rgb_layer("albedo", &beauty())
+                    .channel("depth.Z", depth()));
+                let size = image.layer_data.size;
+                assert_eq!((size.0, size.1), (WIDTH, HEIGHT));
+                assert_eq!(image.layer_data.channel_data.list.len(), 7);
+                let pixels: Vec<_> = beauty().rows().flatten().copied().collect();
+                let expected = [
+                    pixels.iter().map(|c| c.r).collect::<Vec<_>>(),
+                    pixels.iter().map(|c| c.g).collect(),
+                    pixels.iter().map(|c| c.b).collect(),
+                ];
+                for (name, expected) in ["R", "G", "B"].iter().zip(expected) {
+                    assert_eq!(channel(&image, name), expected, "{:?} {:?} {}", pixel_type, compression, name);
+                    assert_eq!(channel(&image, &format!("albedo.{}", name)), expected);
+                }
+                assert_eq!(channel(&image, "depth.Z"), depth());
+            }
+        }
+    }
+}
@@ -0,0 +1,332 @@
+use std::{fs, io};
+use std::io::{BufRead, Read, Write};
+use std::path::Path;
+
+use crate::{check_size, Error, Image};
+
+/// Radiance RGBE images.
+impl Image<f32> {
+    pub fn read_hdr(path: &Path) -> Result<Image<f32>, Error> {
+        let file = fs::File::open(path).map_err(Error::ReadIO)?;
+        Image::read_hdr_from(&mut io::BufReader::new(file))
+    }
+
+    pub fn read_hdr_from(r: &mut impl BufRead) -> Result<Image<f32>, Error> {
+        let mut line = String::new();
+        r.read_line(&mut line).map_err(Error::ReadIO)?;
+        if !line.starts_with("#?") {
+            return Err(Error::Corrupt("missing Radiance signature".to_string()));
+        }
+        loop {
+            line.clear();
+            if r.read_line(&mut line).map_err(Error::ReadIO)? == 0 {
+                return Err(Error::Corrupt("unexpected end of header".to_string()));
+            }
+            let line = line.trim();
+            if line.is_empty() {
+                break;
+            }
+            if let Some(format) = line.strip_prefix("FORMAT=") {
+                if format != "32-bit_rle_rgbe" {
+                    return Err(Error::Unsupported(format!("pixel format {}", format)));
+                }
+            }
+        }
+
+        line.clear();
+        r.read_line(&mut line).map_err(Error::ReadIO)?;
+        let (h, w) = match line.split_whitespace().collect::<Vec<_>>().as_slice() {
+            ["-Y", h, "+X", w] => (h.parse::<usize>(), w.parse::<usize>()),
+            _ => return Err(Error::Unsupported(format!("image orientation {}", line.trim()))),
+        };
+        let (h, w) = match (h, w) {
+            (Ok(h), Ok(w)) if h > 0 && w > 0 => (h, w),
+            _ => return Err(Error::Corrupt(format!("invalid resolution {}", line.trim()))),
+        };
+        check_size(w, h)?;
+
+        let mut rows: Vec<Vec<_>> = Vec::with_capacity(h);
+        for _ in 0..h {
+            rows.push(read_scanline(r, w)?
+                .into_iter()
+                .map(from_rgbe)
+                .collect());
+        }
+        Ok(Image::from(rows))
+    }
+
+    pub fn write_hdr(&self, path: &Path) -> Result<(), Error> {
+        let file = fs::File::create(path)?;
+        let mut writer = io::BufWriter::new(file);
+        self.write_hdr_to(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn write_hdr_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(w, "#?RADIANCE\nFORMAT=32-bit_rle_rgbe\n\n-Y {} +X {}\n", self.h(), self.w())?;
+        for row in self.rows() {
+            let pixels: Vec<_> = row.iter().map(|c| to_rgbe(*c)).collect();
+            if !(8..0x8000).contains(&pixels.len()) {
+                for p in &pixels {
+                    w.write_all(p)?;
+                }
+                continue;
+            }
+            w.write_all(&[2, 2, (pixels.len() >> 8) as u8, pixels.len() as u8])?;
+            for i in 0..4 {
+                let channel: Vec<_> = pixels.iter().map(|p| p[i]).collect();
+                w.write_all(&rle(&channel))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn read_bytes(r: &mut impl Read, buf: &mut [u8]) -> Result<(), Error> {
+    r.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => Error::Corrupt("unexpected end of pixel data".to_string()),
+        _ => Error::ReadIO(e),
+    })
+}
+
+fn read_scanline(r: &mut impl Read, w: usize) -> Result<Vec<[u8; 4]>, Error> {
+    let mut first = [0; 4];
+    read_bytes(r, &mut first)?;
+    let new_rle = first[0] == 2 && first[1] == 2 && first[2] & 0x80 == 0;
+    if !(8..0x8000).contains(&w) || !new_rle {
+        return read_flat_scanline(r, w, first);
+    }
+    if ((first[2] as usize) << 8 | first[3] as usize) != w {
+        return Err(Error::Corrupt("scanline width mismatch".to_string()));
+    }
+
+    let mut pixels = vec![[0; 4]; w];
+    for i in 0..4 {
+        let mut x = 0;
+        while x < w {
+            let mut count = [0];
+            read_bytes(r, &mut count)?;
+            let (count, run) = if count[0] > 128 {
+                (count[0] as usize - 128, true)
+            } else {
+                (count[0] as usize, false)
+            };
+            if count == 0 || x + count > w {
+                return Err(Error::Corrupt("bad scanline run".to_string()));
+            }
+            if run {
+                let mut value = [0];
+                read_bytes(r, &mut value)?;
+                for p in &mut pixels[x..x + count] {
+                    p[i] = value[0];
+                }
+            } else {
+                let mut values = vec![0; count];
+                read_bytes(r, &mut values)?;
+                for (p, v) in pixels[x..x + count].iter_mut().zip(values) {
+                    p[i] = v;
+                }
+            }
+            x += count;
+        }
+    }
+    Ok(pixels)
+}
+
+/// Uncompressed pixels, possibly with the old style `(1, 1, 1, count)` repeats.
+fn read_flat_scanline(r: &mut impl Read, w: usize, first: [u8; 4]) -> Result<Vec<[u8; 4]>, Error> {
+    let mut pixels = vec![first];
+    let mut shift = 0;
+    while pixels.len() < w {
+        let mut p = [0; 4];
+        read_bytes(r, &mut p)?;
+        if p[..3] == [1, 1, 1] {
+            let prev = *pixels.last().unwrap();
+            let count = (p[3] as usize) << shift;
+            if pixels.len() + count > w {
+                return Err(Error::Corrupt("bad scanline run".to_string()));
+            }
+            pixels.extend(std::iter::repeat_n(prev, count));
+            shift += 8;
+        } else {
+            pixels.push(p);
+            shift = 0;
+        }
+    }
+    Ok(pixels)
+}
+
+fn from_rgbe([r, g, b, e]: [u8; 4]) -> color::Color<f32> {
+    if e == 0 {
+        return color::Color { r: 0., g: 0., b: 0. };
+    }
+    let f = 2f32.powi(e as i32 - (128 + 8));
+    color::Color {
+        r: (r as f32 + 0.5) * f,
+        g: (g as f32 + 0.5) * f,
+        b: (b as f32 + 0.5) * f,
+    }
+}
+
+fn to_rgbe(c: color::Color<f32>) -> [u8; 4] {
+    let v = c.r.max(c.g).max(c.b);
+    if v.is_nan() || v < 1e-32 {
+        return [0; 4];
+    }
+    let v = v.min(1e38);
+    let mut e = v.log2().floor() as i32 + 1;
+    // Mantissa of `v` in [0.5, 1) with rounding errors of `log2` corrected.
+    let mut m = v / 2f32.powi(e);
+    if m >= 1. {
+        m /= 2.;
+        e += 1;
+    } else if m < 0.5 {
+        m *= 2.;
+        e -= 1;
+    }
+    let k = m * 256. / v;
+    let channel = |x: f32| (x.max(0.) * k) as u8;
+    [channel(c.r), channel(c.g), channel(c.b), (e + 128) as u8]
+}
+
+/// Runs of at least 4 equal bytes are stored as `(128 + count, byte)`,
+/// other bytes as `(count, bytes...)`.
+fn rle(data: &[u8]) -> Vec<u8> {
+    const MIN_RUN: usize = 4;
+    const MAX_RUN: usize = 127;
+    const MAX_LITERAL: usize = 128;
+    let mut res = vec![];
+    let mut cur = 0;
+    while cur < data.len() {
+        let mut run_start = cur;
+        let mut run = 0;
+        while run < MIN_RUN && run_start < data.len() {
+            run_start += run;
+            run = 1;
+            while run_start + run < data.len()
+                && run < MAX_RUN
+                && data[run_start] == data[run_start + run] {
+                run += 1;
+            }
+        }
+        if run < MIN_RUN {
+            run_start = data.len();
+            run = 0;
+        }
+        while cur < run_start {
+            let n = (run_start - cur).min(MAX_LITERAL);
+            res.push(n as u8);
+            res.extend_from_slice(&data[cur..cur + n]);
+            cur += n;
+        }
+        if run > 0 {
+            res.push((128 + run) as u8);
+            res.push(data[run_start]);
+            cur += run;
+        }
+    }
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Flat areas for runs next to values spanning many exponents.
+    fn image(w: usize, h: usize) -> Image<f32> {
+        let rows: Vec<Vec<_>> = (0..h)
+            .map(|y| (0..w)
+                .map(|x| if x < w / 2 {
+                    color::Color { r: 1., g: 0.5, b: 0.25 }
+                } else {
+                    let v = ((x * 7 + y * 13) % 19) as f32;
+                    color::Color { r: 2f32.powf(v - 16.), g: v / 19., b: 0. }
+                })
+                .collect())
+            .collect();
+        Image::from(rows)
+    }
+
+    fn round_trip(image: &Image<f32>) -> Image<f32> {
+        let mut bytes = vec![];
+        image.write_hdr_to(&mut bytes).unwrap();
+        Image::read_hdr_from(&mut bytes.as_slice()).unwrap()
+    }
+
+    #[test]
+    fn write_read() {
+        // Narrow scanlines are stored flat, wide ones with RLE.
+        for (w, h) in [(5, 3), (300, 4)] {
+            let original = image(w, h);
+            let read = round_trip(&original);
+            assert_eq!((read.w(), read.h()), (w, h));
+            // Channels share the exponent of the largest one and keep 8 bits of it.
+            for (a, b) in original.rows().flatten().zip(read.rows().flatten()) {
+                let max = a.r.max(a.g).max(a.b);
+                for (x, y) in [(a.r, b.r), (a.g, b.g), (a.b, b.b)] {
+                    assert!((x - y).abs() <= max / 128., "{} != {}", x, y);
+                }
+            }
+            assert!(original.rmse(&read) < 4e-3);
+        }
+    }
+
+    #[test]
+    fn rgbe() {
+        assert_eq!(to_rgbe(color::Color { r: 1., g: 0.5, b: 0. }), [128, 64, 0, 129]);
+        assert_eq!(to_rgbe(color::Color { r: 0., g: 0., b: 0. }), [0; 4]);
+        let c = from_rgbe([128, 64, 0, 129]);
+        assert_eq!((c.r, c.g, c.b), (1. + 1. / 256., 0.5 + 1. / 256., 1. / 256.));
+    }
+
+    #[test]
+    fn read_rle_scanline() {
+        // Width 10: run of 6 and 4 literals per channel, exponent as a single run.
+        let mut data = vec![2, 2, 0, 10];
+        for value in [128, 64, 0] {
+            data.extend_from_slice(&[128 + 6, value, 4, 1, 2, 3, 4]);
+        }
+        data.extend_from_slice(&[128 + 10, 129]);
+        let pixels = read_scanline(&mut data.as_slice(), 10).unwrap();
+        assert_eq!(pixels[0], [128, 64, 0, 129]);
+        assert_eq!(pixels[5], [128, 64, 0, 129]);
+        assert_eq!(pixels[9], [4, 4, 4, 129]);
+    }
+
+    #[test]
+    fn rle_long_runs() {
+        let data: Vec<u8> = std::iter::repeat_n(7, 300).chain(0..=200).chain(std::iter::repeat_n(1, 5)).collect();
+        let mut line = vec![2, 2, (data.len() >> 8) as u8, data.len() as u8];
+        for _ in 0..4 {
+            line.extend(rle(&data));
+        }
+        let pixels = read_scanline(&mut line.as_slice(), data.len()).unwrap();
+        assert!(pixels.iter().zip(&data).all(|(p, d)| *p == [*d; 4]));
+    }
+
+    #[test]
+    fn read_old_style_repeats() {
+        // Pixel repeated 2 + (1 << 8) times by two consecutive repeat markers.
+        let data = [10, 20, 30, 130, 1, 1, 1, 2, 1, 1, 1, 1];
+        let pixels = read_flat_scanline(&mut &data[4..], 259, [10, 20, 30, 130]).unwrap();
+        assert_eq!(pixels.len(), 259);
+        assert!(pixels.iter().all(|p| *p == [10, 20, 30, 130]));
+    }
+
+    #[test]
+    fn corrupt() {
+        let mut bytes = vec![];
+        image(20, 2).write_hdr_to(&mut bytes).unwrap();
+        bytes.truncate(bytes.len() - 3);
+        assert!(matches!(Image::read_hdr_from(&mut bytes.as_slice()), Err(Error::Corrupt(_))));
+        let bytes = b"#?RADIANCE\n\n-Y 1 +X 8\n\x02\x02\x00\x09";
+        assert!(matches!(Image::read_hdr_from(&mut &bytes[..]), Err(Error::Corrupt(_))));
+        assert!(matches!(Image::read_hdr_from(&mut &b"P6\n"[..]), Err(Error::Corrupt(_))));
+    }
+
+    #[test]
+    fn oversized() {
+        let header = b"#?RADIANCE\n\n-Y 99999999999999 +X 8\n";
+        assert!(matches!(Image::read_hdr_from(&mut &header[..]), Err(Error::Unsupported(_))));
+    }
+}
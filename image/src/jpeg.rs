@@ -0,0 +1,613 @@
+use std::f32::consts::PI;
+use std::fs;
+use std::path::Path;
+
+use crate::{check_size, Error, Image};
+
+/// Baseline and extended sequential JPEG images with Huffman coding.
+impl Image<f32> {
+    /// Reads channel values scaled to `[0, 1]` without decoding the transfer function.
+    pub fn read_jpeg(path: &Path) -> Result<Image<f32>, Error> {
+        let data = fs::read(path).map_err(Error::ReadIO)?;
+        Image::read_jpeg_from(&data)
+    }
+
+    pub fn read_jpeg_from(data: &[u8]) -> Result<Image<f32>, Error> {
+        Decoder::default().decode(data)
+    }
+}
+
+/// Natural order indices of coefficients in zigzag order.
+const ZIGZAG: [usize; 64] = [
+    0, 1, 8, 16, 9, 2, 3, 10,
+    17, 24, 32, 25, 18, 11, 4, 5,
+    12, 19, 26, 33, 40, 48, 41, 34,
+    27, 20, 13, 6, 7, 14, 21, 28,
+    35, 42, 49, 56, 57, 50, 43, 36,
+    29, 22, 15, 23, 30, 37, 44, 51,
+    58, 59, 52, 45, 38, 31, 39, 46,
+    53, 60, 61, 54, 47, 55, 62, 63,
+];
+
+fn corrupt<T>(msg: &str) -> Result<T, Error> {
+    Err(Error::Corrupt(msg.to_string()))
+}
+
+fn unsupported<T>(msg: &str) -> Result<T, Error> {
+    Err(Error::Unsupported(msg.to_string()))
+}
+
+#[derive(Default)]
+struct Decoder {
+    width: usize,
+    height: usize,
+    components: Vec<Component>,
+    /// Quantization tables in zigzag order.
+    quantization: [Option<[u16; 64]>; 4],
+    dc_tables: [Option<Huffman>; 4],
+    ac_tables: [Option<Huffman>; 4],
+    restart_interval: usize,
+    /// Adobe transform flag, components are stored as RGB when it is zero.
+    adobe_transform: Option<u8>,
+}
+
+struct Component {
+    id: u8,
+    h: usize,
+    v: usize,
+    quantization: usize,
+    dc_table: usize,
+    ac_table: usize,
+    dc_pred: i32,
+    /// Samples of whole blocks, wider and taller than the image.
+    plane: Vec<u8>,
+    plane_w: usize,
+    decoded: bool,
+}
+
+impl Decoder {
+    fn decode(mut self, data: &[u8]) -> Result<Image<f32>, Error> {
+        if !data.starts_with(&[0xFF, 0xD8]) {
+            return corrupt("missing JPEG signature");
+        }
+        let mut pos = 2;
+        loop {
+            while data.get(pos) == Some(&0xFF) && data.get(pos + 1) == Some(&0xFF) {
+                pos += 1;
+            }
+            let marker = match data.get(pos..pos + 2) {
+                Some([0xFF, m]) => *m,
+                // Some encoders leave out the final marker.
+                None if !self.components.is_empty() => break,
+                _ => return corrupt("marker expected"),
+            };
+            pos += 2;
+            if marker == 0xD9 {
+                break;
+            }
+            if (0xD0..=0xD7).contains(&marker) || marker == 0x01 {
+                continue;
+            }
+            let len = match data.get(pos..pos + 2) {
+                Some(&[a, b]) => (a as usize) << 8 | b as usize,
+                _ => return corrupt("unexpected end of data"),
+            };
+            let segment = match data.get(pos + 2..pos + len) {
+                Some(segment) if len >= 2 => segment,
+                _ => return corrupt("unexpected end of data"),
+            };
+            pos += len;
+            match marker {
+                0xC0 | 0xC1 => self.frame(segment)?,
+                0xC2 | 0xC6 | 0xCA | 0xCE => return unsupported("progressive JPEG"),
+                0xC3 | 0xC5 | 0xC7 | 0xCB | 0xCF => return unsupported("lossless JPEG"),
+                0xC9 | 0xCD => return unsupported("arithmetic coded JPEG"),
+                0xC4 => self.huffman_tables(segment)?,
+                0xDB => self.quantization_tables(segment)?,
+                0xDD => {
+                    if segment.len() < 2 {
+                        return corrupt("bad restart interval");
+                    }
+                    self.restart_interval = (segment[0] as usize) << 8 | segment[1] as usize;
+                }
+                0xDA => pos = self.scan(segment, data, pos)?,
+                0xEE if segment.starts_with(b"Adobe") && segment.len() >= 12 => {
+                    self.adobe_transform = Some(segment[11]);
+                }
+                // Application data and comments.
+                _ => {}
+            }
+        }
+        self.image()
+    }
+
+    fn frame(&mut self, s: &[u8]) -> Result<(), Error> {
+        if !self.components.is_empty() {
+            return unsupported("multiple frames");
+        }
+        if s.len() < 6 {
+            return corrupt("bad frame header");
+        }
+        if s[0] != 8 {
+            return unsupported(&format!("{}-bit samples", s[0]));
+        }
+        self.height = (s[1] as usize) << 8 | s[2] as usize;
+        self.width = (s[3] as usize) << 8 | s[4] as usize;
+        if self.width == 0 || self.height == 0 {
+            return unsupported("image height defined by the DNL marker");
+        }
+        check_size(self.width, self.height)?;
+        let n = s[5] as usize;
+        if s.len() < 6 + 3 * n || n == 0 {
+            return corrupt("bad frame header");
+        }
+        if n != 1 && n != 3 {
+            return unsupported(&format!("{} color components", n));
+        }
+        for c in s[6..6 + 3 * n].chunks_exact(3) {
+            let (h, v) = ((c[1] >> 4) as usize, (c[1] & 15) as usize);
+            if !(1..=4).contains(&h) || !(1..=4).contains(&v) || c[2] > 3 {
+                return corrupt("bad frame component");
+            }
+            self.components.push(Component {
+                id: c[0],
+                h,
+                v,
+                quantization: c[2] as usize,
+                dc_table: 0,
+                ac_table: 0,
+                dc_pred: 0,
+                plane: vec![],
+                plane_w: 0,
+                decoded: false,
+            });
+        }
+        let (mcu_w, mcu_h) = self.mcu_size();
+        let mcus_x = self.width.div_ceil(mcu_w);
+        let mcus_y = self.height.div_ceil(mcu_h);
+        for c in &mut self.components {
+            c.plane_w = mcus_x * c.h * 8;
+            c.plane = vec![0; c.plane_w * mcus_y * c.v * 8];
+        }
+        Ok(())
+    }
+
+    fn max_sampling(&self) -> (usize, usize) {
+        let h = self.components.iter().map(|c| c.h).max().unwrap_or(1);
+        let v = self.components.iter().map(|c| c.v).max().unwrap_or(1);
+        (h, v)
+    }
+
+    fn mcu_size(&self) -> (usize, usize) {
+        let (h, v) = self.max_sampling();
+        (8 * h, 8 * v)
+    }
+
+    fn huffman_tables(&mut self, mut s: &[u8]) -> Result<(), Error> {
+        while !s.is_empty() {
+            if s.len() < 17 {
+                return corrupt("bad Huffman table");
+            }
+            let (class, id) = (s[0] >> 4, (s[0] & 15) as usize);
+            let mut counts = [0; 16];
+            counts.copy_from_slice(&s[1..17]);
+            let total: usize = counts.iter().map(|n| *n as usize).sum();
+            if class > 1 || id > 3 || s.len() < 17 + total {
+                return corrupt("bad Huffman table");
+            }
+            let table = Huffman::new(counts, s[17..17 + total].to_vec())?;
+            if class == 0 {
+                self.dc_tables[id] = Some(table);
+            } else {
+                self.ac_tables[id] = Some(table);
+            }
+            s = &s[17 + total..];
+        }
+        Ok(())
+    }
+
+    fn quantization_tables(&mut self, mut s: &[u8]) -> Result<(), Error> {
+        while !s.is_empty() {
+            let (precision, id) = (s[0] >> 4, (s[0] & 15) as usize);
+            let size = if precision == 0 { 64 } else { 128 };
+            if precision > 1 || id > 3 || s.len() < 1 + size {
+                return corrupt("bad quantization table");
+            }
+            let mut table = [0; 64];
+            for (i, q) in table.iter_mut().enumerate() {
+                *q = if precision == 0 {
+                    s[1 + i] as u16
+                } else {
+                    (s[1 + 2 * i] as u16) << 8 | s[2 + 2 * i] as u16
+                };
+            }
+            self.quantization[id] = Some(table);
+            s = &s[1 + size..];
+        }
+        Ok(())
+    }
+
+    /// Decodes entropy coded data after the scan header, returns position past it.
+    fn scan(&mut self, s: &[u8], data: &[u8], pos: usize) -> Result<usize, Error> {
+        if self.components.is_empty() {
+            return corrupt("scan before frame header");
+        }
+        let n = s.first().copied().unwrap_or(0) as usize;
+        if n == 0 || s.len() < 1 + 2 * n + 3 {
+            return corrupt("bad scan header");
+        }
+        let mut scan = Vec::with_capacity(n);
+        for c in s[1..1 + 2 * n].chunks_exact(2) {
+            let i = match self.components.iter().position(|comp| comp.id == c[0]) {
+                Some(i) => i,
+                None => return corrupt("unknown scan component"),
+            };
+            let comp = &mut self.components[i];
+            comp.dc_table = (c[1] >> 4) as usize;
+            comp.ac_table = (c[1] & 15) as usize;
+            comp.dc_pred = 0;
+            if comp.dc_table > 3 || comp.ac_table > 3 {
+                return corrupt("bad scan component");
+            }
+            scan.push(i);
+        }
+        let (ss, se, a) = (s[1 + 2 * n], s[2 + 2 * n], s[3 + 2 * n]);
+        if ss != 0 || se != 63 || a != 0 {
+            return unsupported("progressive JPEG");
+        }
+
+        let (h_max, v_max) = self.max_sampling();
+        let (blocks_x, blocks_y) = if let [i] = scan[..] {
+            // Non-interleaved scans cover the component in single blocks.
+            let c = &self.components[i];
+            (
+                (self.width * c.h).div_ceil(h_max).div_ceil(8),
+                (self.height * c.v).div_ceil(v_max).div_ceil(8),
+            )
+        } else {
+            (self.width.div_ceil(8 * h_max), self.height.div_ceil(8 * v_max))
+        };
+        let mut bits = Bits { data, pos, acc: 0, n: 0, marker: false };
+        let mut block = [0f32; 64];
+        let cos = idct_table();
+        for mcu in 0..blocks_x * blocks_y {
+            if self.restart_interval > 0 && mcu > 0 && mcu % self.restart_interval == 0 {
+                bits.restart()?;
+                for i in &scan {
+                    self.components[*i].dc_pred = 0;
+                }
+            }
+            let (mcu_x, mcu_y) = (mcu % blocks_x, mcu / blocks_x);
+            for i in &scan {
+                let (h, v) = if scan.len() == 1 {
+                    (1, 1)
+                } else {
+                    (self.components[*i].h, self.components[*i].v)
+                };
+                for by in 0..v {
+                    for bx in 0..h {
+                        self.block(*i, &mut bits, &mut block)?;
+                        let c = &mut self.components[*i];
+                        let x = (mcu_x * h + bx) * 8;
+                        let y = (mcu_y * v + by) * 8;
+                        idct(&block, &cos, &mut c.plane[y * c.plane_w + x..], c.plane_w);
+                    }
+                }
+            }
+        }
+        for i in &scan {
+            self.components[*i].decoded = true;
+        }
+        Ok(bits.pos)
+    }
+
+    /// Dequantized coefficients of the next block in natural order.
+    fn block(&mut self, i: usize, bits: &mut Bits, block: &mut [f32; 64]) -> Result<(), Error> {
+        let c = &mut self.components[i];
+        let (dc, ac, q) = match (
+            &self.dc_tables[c.dc_table],
+            &self.ac_tables[c.ac_table],
+            &self.quantization[c.quantization],
+        ) {
+            (Some(dc), Some(ac), Some(q)) => (dc, ac, q),
+            _ => return corrupt("missing table"),
+        };
+        *block = [0.; 64];
+        let t = dc.decode(bits)?;
+        c.dc_pred = match c.dc_pred.checked_add(bits.receive_extend(t)?) {
+            Some(dc) => dc,
+            None => return corrupt("DC coefficient overflow"),
+        };
+        // Products of garbage coefficients may not fit into `i32`.
+        block[0] = c.dc_pred as f32 * q[0] as f32;
+        let mut k = 1;
+        while k < 64 {
+            let rs = ac.decode(bits)?;
+            let (r, s) = ((rs >> 4) as usize, rs & 15);
+            if s == 0 {
+                if r != 15 {
+                    break;
+                }
+                k += 16;
+                continue;
+            }
+            k += r;
+            if k > 63 {
+                return corrupt("coefficient index out of block");
+            }
+            block[ZIGZAG[k]] = bits.receive_extend(s)? as f32 * q[k] as f32;
+            k += 1;
+        }
+        Ok(())
+    }
+
+    fn image(&self) -> Result<Image<f32>, Error> {
+        if self.components.is_empty() || self.components.iter().any(|c| !c.decoded) {
+            return corrupt("missing scan data");
+        }
+        let (h_max, v_max) = self.max_sampling();
+        let sample = |c: &Component, x: usize, y: usize| {
+            c.plane[y * c.v / v_max * c.plane_w + x * c.h / h_max] as f32
+        };
+        let rgb = self.adobe_transform == Some(0);
+        let rows = (0..self.height)
+            .map(|y| (0..self.width)
+                .map(|x| match &self.components[..] {
+                    [gray] => {
+                        let l = sample(gray, x, y) / 255.;
+                        color::Color { r: l, g: l, b: l }
+                    }
+                    [a, b, c] if rgb => color::Color {
+                        r: sample(a, x, y) / 255.,
+                        g: sample(b, x, y) / 255.,
+                        b: sample(c, x, y) / 255.,
+                    },
+                    [luma, cb, cr] => {
+                        let l = sample(luma, x, y);
+                        let cb = sample(cb, x, y) - 128.;
+                        let cr = sample(cr, x, y) - 128.;
+                        let channel = |x: f32| x.round().clamp(0., 255.) / 255.;
+                        color::Color {
+                            r: channel(l + 1.402 * cr),
+                            g: channel(l - 0.344_136 * cb - 0.714_136 * cr),
+                            b: channel(l + 1.772 * cb),
+                        }
+                    }
+                    _ => unreachable!("Component count is checked by the frame header"),
+                })
+                .collect())
+            .collect::<Vec<_>>();
+        Ok(Image::from(rows))
+    }
+}
+
+/// Scaled cosines of the inverse DCT indexed by sample and frequency.
+fn idct_table() -> [[f32; 8]; 8] {
+    let mut cos = [[0f32; 8]; 8];
+    for (x, row) in cos.iter_mut().enumerate() {
+        for (u, c) in row.iter_mut().enumerate() {
+            let scale = if u == 0 { 0.5f32.sqrt() } else { 1. };
+            *c = 0.5 * scale * ((2 * x + 1) as f32 * u as f32 * PI / 16.).cos();
+        }
+    }
+    cos
+}
+
+/// Separable inverse DCT of the block written into 8 rows of the plane.
+fn idct(block: &[f32; 64], cos: &[[f32; 8]; 8], out: &mut [u8], stride: usize) {
+    let mut tmp = [0f32; 64];
+    for v in 0..8 {
+        for x in 0..8 {
+            tmp[v * 8 + x] = (0..8).map(|u| cos[x][u] * block[v * 8 + u]).sum();
+        }
+    }
+    for y in 0..8 {
+        for x in 0..8 {
+            let s: f32 = (0..8).map(|v| cos[y][v] * tmp[v * 8 + x]).sum();
+            out[y * stride + x] = (s + 128.).round().clamp(0., 255.) as u8;
+        }
+    }
+}
+
+struct Huffman {
+    values: Vec<u8>,
+    /// Largest code of each length, `-1` when there are none.
+    max_code: [i32; 17],
+    /// Index of the first value with the code of each length.
+    offset: [i32; 17],
+}
+
+impl Huffman {
+    fn new(counts: [u8; 16], values: Vec<u8>) -> Result<Huffman, Error> {
+        let mut max_code = [-1; 17];
+        let mut offset = [0; 17];
+        let mut code = 0;
+        let mut k = 0;
+        for len in 1..=16 {
+            let n = counts[len - 1] as i32;
+            offset[len] = k - code;
+            code += n;
+            k += n;
+            if n > 0 {
+                max_code[len] = code - 1;
+            }
+            if code > 1 << len {
+                return corrupt("bad Huffman table");
+            }
+            code <<= 1;
+        }
+        Ok(Huffman { values, max_code, offset })
+    }
+
+    fn decode(&self, bits: &mut Bits) -> Result<u8, Error> {
+        let mut code = 0;
+        for len in 1..=16 {
+            code = code << 1 | bits.bit()? as i32;
+            if code <= self.max_code[len] {
+                return Ok(self.values[(self.offset[len] + code) as usize]);
+            }
+        }
+        corrupt("bad Huffman code")
+    }
+}
+
+/// Reader of entropy coded bits, stops at markers.
+struct Bits<'a> {
+    data: &'a [u8],
+    pos: usize,
+    acc: u32,
+    n: u32,
+    marker: bool,
+}
+
+impl Bits<'_> {
+    fn bit(&mut self) -> Result<u32, Error> {
+        if self.n == 0 {
+            let byte = if self.marker {
+                0
+            } else {
+                match self.data.get(self.pos) {
+                    None => return corrupt("unexpected end of data"),
+                    Some(0xFF) if self.data.get(self.pos + 1) != Some(&0) => {
+                        self.marker = true;
+                        0
+                    }
+                    Some(0xFF) => {
+                        self.pos += 2;
+                        0xFF
+                    }
+                    Some(b) => {
+                        self.pos += 1;
+                        *b
+                    }
+                }
+            };
+            self.acc = byte as u32;
+            self.n = 8;
+        }
+        self.n -= 1;
+        Ok(self.acc >> self.n & 1)
+    }
+
+    /// Signed value of `s` bits.
+    fn receive_extend(&mut self, s: u8) -> Result<i32, Error> {
+        if s > 16 {
+            return corrupt("bad coefficient size");
+        }
+        let mut v = 0;
+        for _ in 0..s {
+            v = v << 1 | self.bit()? as i32;
+        }
+        Ok(if s > 0 && v < 1 << (s - 1) { v - (1 << s) + 1 } else { v })
+    }
+
+    fn restart(&mut self) -> Result<(), Error> {
+        self.n = 0;
+        self.marker = false;
+        while self.data.get(self.pos) == Some(&0xFF) && self.data.get(self.pos + 1) == Some(&0xFF) {
+            self.pos += 1;
+        }
+        match self.data.get(self.pos..self.pos + 2) {
+            Some([0xFF, m]) if (0xD0..=0xD7).contains(m) => {
+                self.pos += 2;
+                Ok(())
+            }
+            _ => corrupt("restart marker expected"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn segment(marker: u8, body: &[u8]) -> Vec<u8> {
+        let mut s = vec![0xFF, marker, ((body.len() + 2) >> 8) as u8, (body.len() + 2) as u8];
+        s.extend_from_slice(body);
+        s
+    }
+
+    fn unit_quantization() -> Vec<u8> {
+        [[0].as_ref(), &[1; 64]].concat()
+    }
+
+    /// DC differences of 8 and 7 bits are encoded as `0` and `1`.
+    fn dc_table() -> Vec<u8> {
+        [[0x00, 2].as_ref(), &[0; 15], &[8, 7]].concat()
+    }
+
+    /// The only code `0` is the end of block.
+    fn ac_table() -> Vec<u8> {
+        [[0x10, 1].as_ref(), &[0; 15], &[0x00]].concat()
+    }
+
+    fn gray_frame(width: u16, height: u16) -> Vec<u8> {
+        let [w0, w1] = width.to_be_bytes();
+        let [h0, h1] = height.to_be_bytes();
+        vec![8, h0, h1, w0, w1, 1, 1, 0x11, 0]
+    }
+
+    /// Grayscale baseline image with the tables above.
+    fn gray(width: u16, height: u16, scan: &[u8]) -> Vec<u8> {
+        let mut data = vec![0xFF, 0xD8];
+        data.extend(segment(0xDB, &unit_quantization()));
+        data.extend(segment(0xC0, &gray_frame(width, height)));
+        data.extend(segment(0xC4, &dc_table()));
+        data.extend(segment(0xC4, &ac_table()));
+        data.extend(segment(0xDA, &[1, 1, 0x00, 0, 63, 0]));
+        data.extend_from_slice(scan);
+        data.extend_from_slice(&[0xFF, 0xD9]);
+        data
+    }
+
+    /// Two blocks: DC 128 (`0 10000000 0`) and DC 128 - 64 (`1 0111111 0`) padded with ones.
+    const TWO_BLOCKS: [u8; 3] = [0b0100_0000, 0b0010_1111, 0b1101_1111];
+
+    #[test]
+    fn flat_blocks() {
+        let image = Image::read_jpeg_from(&gray(16, 8, &TWO_BLOCKS)).unwrap();
+        assert_eq!((image.w(), image.h()), (16, 8));
+        // Flat blocks get `128 + DC / 8`.
+        for row in image.rows() {
+            for (x, c) in row.iter().enumerate() {
+                let expected = if x < 8 { 144. } else { 136. } / 255.;
+                assert_eq!((c.r, c.g, c.b), (expected, expected, expected));
+            }
+        }
+    }
+
+    #[test]
+    fn truncated() {
+        let data = gray(16, 8, &TWO_BLOCKS);
+        // Only the final marker may be left out.
+        for len in 0..data.len() - 2 {
+            assert!(matches!(Image::read_jpeg_from(&data[..len]), Err(Error::Corrupt(_))), "{}", len);
+        }
+        assert!(Image::read_jpeg_from(&data[..data.len() - 2]).is_ok());
+    }
+
+    #[test]
+    fn corrupt_data() {
+        let mut data = gray(16, 8, &TWO_BLOCKS);
+        assert!(matches!(Image::read_jpeg_from(&data[1..]), Err(Error::Corrupt(_))));
+        // Scan referring to the quantization table that is not defined.
+        let frame = data.windows(2).position(|w| w == [0xFF, 0xC0]).unwrap();
+        data[frame + 12] = 1;
+        assert!(matches!(Image::read_jpeg_from(&data), Err(Error::Corrupt(_))));
+        let too_large = gray(u16::MAX, u16::MAX, &TWO_BLOCKS);
+        assert!(matches!(Image::read_jpeg_from(&too_large), Err(Error::Unsupported(_))));
+    }
+
+    #[test]
+    fn dc_overflow() {
+        let mut decoder = Decoder::default();
+        decoder.frame(&gray_frame(16, 8)).unwrap();
+        decoder.quantization_tables(&unit_quantization()).unwrap();
+        decoder.huffman_tables(&dc_table()).unwrap();
+        decoder.huffman_tables(&ac_table()).unwrap();
+        decoder.components[0].dc_pred = i32::MAX - 100;
+        let mut bits = Bits { data: &TWO_BLOCKS, pos: 0, acc: 0, n: 0, marker: false };
+        let mut block = [0.; 64];
+        assert!(matches!(decoder.block(0, &mut bits, &mut block), Err(Error::Corrupt(_))));
+    }
+}
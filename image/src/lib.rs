@@ -1,17 +1,60 @@
 extern crate color;
+extern crate flate2;
+extern crate half;
+extern crate num_traits;
 extern crate png;
 
-use std::{fs, io};
+use std::{fmt, fs, io};
 use std::num::NonZeroUsize;
 use std::ops::{Index, IndexMut};
 use std::path::Path;
 
+use color::{Encoding, ToneMap};
+use num_traits::{Num, Zero};
+
+pub use exr::{ExrCompression, ExrPixelType, ExrWriter};
+
+mod exr;
+mod hdr;
+mod jpeg;
+mod pfm;
+mod pnm;
+
 pub type Color = color::Color<u8>;
 
+/// Larger images are refused before allocating memory for them.
+const MAX_PIXELS: usize = 1 << 26;
+
+/// Number of pixels of the image with the size read from a file.
+fn check_size(w: usize, h: usize) -> Result<usize, Error> {
+    match w.checked_mul(h) {
+        Some(n) if n <= MAX_PIXELS => Ok(n),
+        _ => Err(Error::Unsupported(format!("{}x{} image is too large", w, h))),
+    }
+}
+
+#[derive(Debug)]
 pub enum Error {
-    WriteIO(io::Error)
+    WriteIO(io::Error),
+    ReadIO(io::Error),
+    /// File contents do not follow the format.
+    Corrupt(String),
+    /// Valid file using a feature the decoder does not support.
+    Unsupported(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Error::WriteIO(e) | Error::ReadIO(e) => write!(f, "{}", e),
+            Error::Corrupt(msg) => write!(f, "corrupt image: {}", msg),
+            Error::Unsupported(msg) => write!(f, "unsupported image: {}", msg),
+        }
+    }
 }
 
+impl std::error::Error for Error {}
+
 impl From<io::Error> for Error {
     fn from(e: io::Error) -> Error {
         Error::WriteIO(e)
@@ -22,36 +65,69 @@ impl From<png::EncodingError> for Error {
     fn from(e: png::EncodingError) -> Error {
         match e {
             png::EncodingError::IoError(e) => Error::WriteIO(e),
-            png::EncodingError::Format(e) =>
-                panic!("Unable to encode image as png \
-                        (inconsistent image state): {}", e)
+            png::EncodingError::Format(e) => Error::Unsupported(e.to_string()),
         }
     }
 }
 
+impl From<png::DecodingError> for Error {
+    fn from(e: png::DecodingError) -> Error {
+        match e {
+            png::DecodingError::IoError(e) => Error::ReadIO(e),
+            png::DecodingError::LimitsExceeded => Error::Unsupported("image is too large".to_string()),
+            e => Error::Corrupt(e.to_string()),
+        }
+    }
+}
+
+/// Integer channel of low dynamic range images.
+pub trait Sample: Num + Copy {
+    const BITS: u8;
+
+    /// Quantizes value from `[0, 1]`.
+    fn quantize(x: f64) -> Self;
+
+    fn push_be(self, bytes: &mut Vec<u8>);
+}
+
+impl Sample for u8 {
+    const BITS: u8 = 8;
+
+    fn quantize(x: f64) -> Self {
+        (x.clamp(0., 1.) * u8::MAX as f64).round() as u8
+    }
+
+    fn push_be(self, bytes: &mut Vec<u8>) {
+        bytes.push(self);
+    }
+}
+
+impl Sample for u16 {
+    const BITS: u8 = 16;
+
+    fn quantize(x: f64) -> Self {
+        (x.clamp(0., 1.) * u16::MAX as f64).round() as u16
+    }
+
+    fn push_be(self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.to_be_bytes());
+    }
+}
+
+/// Rows of pixels, 8-bit by default. Floating point images keep linear
+/// radiance where `1.0` stands for white.
 #[derive(Clone, Debug)]
-pub struct Image(Vec<Vec<Color>>);
+pub struct Image<T: Num = u8>(Vec<Vec<color::Color<T>>>);
 
-impl Image {
+impl<T: Num + Zero + Copy> Image<T> {
     pub fn from_size(w: NonZeroUsize, h: NonZeroUsize) -> Self {
+        let black = color::Color { r: T::zero(), g: T::zero(), b: T::zero() };
         Image(vec![
-            vec![Color::black(); usize::from(w)];
+            vec![black; usize::from(w)];
             usize::from(h)
         ])
     }
 
-    pub fn write_png(&self, path: &Path) -> Result<(), Error> {
-        let file = fs::File::create(path)?;
-        let writer = io::BufWriter::new(file);
-        let mut encoder = png::Encoder::new(writer, self.w() as u32, self.h() as u32);
-        encoder.set_color(png::ColorType::RGB);
-        encoder.set_depth(png::BitDepth::Eight);
-        let data = self.linearized();
-        Ok(encoder
-            .write_header()?
-            .write_image_data(&data)?)
-    }
-
     pub fn h(&self) -> usize {
         self.0.len()
     }
@@ -60,6 +136,181 @@ impl Image {
         self.0[0].len()
     }
 
+    pub fn set_row(&mut self, index: usize, row: Vec<color::Color<T>>) -> &mut Self {
+        self.0[index] = row;
+        self
+    }
+
+    pub fn rows(&self) -> impl Iterator<Item=&Vec<color::Color<T>>> {
+        self.0.iter()
+    }
+
+    pub fn map<U: Num>(&self, f: impl Fn(color::Color<T>) -> color::Color<U>) -> Image<U> {
+        Image(self.0
+            .iter()
+            .map(|row| row.iter().map(|c| f(*c)).collect())
+            .collect())
+    }
+}
+
+impl Image<f32> {
+    /// Scales radiance by `2 ^ exposure` and compresses it into `[0, 1]`.
+    pub fn tone_mapped(&self, tone_map: ToneMap, exposure: f64) -> Image<f32> {
+        let k = 2f64.powf(exposure);
+        self.map(|c| color::Color::from(tone_map.apply(k * c)))
+    }
+
+    /// Quantizes radiance to 8 bits with the given transfer function.
+    pub fn to_ldr(&self, encoding: Encoding) -> Image<u8> {
+        self.quantize(encoding)
+    }
+
+    /// Quantizes radiance to 16 bits with the given transfer function.
+    pub fn to_ldr16(&self, encoding: Encoding) -> Image<u16> {
+        self.quantize(encoding)
+    }
+
+    fn quantize<T: Sample>(&self, encoding: Encoding) -> Image<T> {
+        self.map(|c| {
+            let c = color::Color::<f64>::from(c).encode(encoding);
+            color::Color { r: T::quantize(c.r), g: T::quantize(c.g), b: T::quantize(c.b) }
+        })
+    }
+
+    /// Applies inverse of the transfer function, e.g. to get radiance of 8-bit images.
+    pub fn decoded(&self, encoding: Encoding) -> Image<f32> {
+        self.map(|c| color::Color::from(color::Color::<f64>::from(c).decode(encoding)))
+    }
+
+    /// Reads radiance from an image file chosen by its extension. Radiance `.hdr` and `.pfm`
+    /// files are linear, `.png` and `.jpg` ones are decoded from sRGB.
+    pub fn read_linear(path: &Path) -> Result<Image<f32>, Error> {
+        let extension = path.extension().and_then(|e| e.to_str()).map(str::to_lowercase);
+        match extension.as_deref() {
+            Some("hdr") => Image::read_hdr(path),
+            Some("pfm") => Image::read_pfm(path),
+            Some("png") => Ok(Image::read_png(path)?.decoded(Encoding::Srgb)),
+            Some("jpg") | Some("jpeg") => Ok(Image::read_jpeg(path)?.decoded(Encoding::Srgb)),
+            _ => Err(Error::Unsupported(format!("unknown image format of {}", path.display()))),
+        }
+    }
+
+    /// Reads 8 or 16-bit PNG with channel values scaled to `[0, 1]`
+    /// without decoding the transfer function. Alpha channel is ignored.
+    pub fn read_png(path: &Path) -> Result<Image<f32>, Error> {
+        let file = fs::File::open(path).map_err(Error::ReadIO)?;
+        Image::read_png_from(io::BufReader::new(file))
+    }
+
+    pub fn read_png_from(r: impl io::Read) -> Result<Image<f32>, Error> {
+        let mut decoder = png::Decoder::new(r);
+        // Palette, low bit depth and transparency chunks are expanded to 8-bit samples.
+        decoder.set_transformations(png::Transformations::EXPAND);
+        let (info, mut reader) = decoder.read_info()?;
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data)?;
+
+        let channels = match info.color_type {
+            png::ColorType::Grayscale => 1,
+            png::ColorType::GrayscaleAlpha => 2,
+            png::ColorType::RGB => 3,
+            png::ColorType::RGBA => 4,
+            png::ColorType::Indexed => return Err(Error::Unsupported("unexpanded palette".to_string())),
+        };
+        let samples: Vec<f32> = match info.bit_depth {
+            png::BitDepth::Eight => data.iter().map(|x| *x as f32 / u8::MAX as f32).collect(),
+            png::BitDepth::Sixteen => data
+                .chunks_exact(2)
+                .map(|x| u16::from_be_bytes([x[0], x[1]]) as f32 / u16::MAX as f32)
+                .collect(),
+            depth => return Err(Error::Unsupported(format!("{}-bit samples", depth as u8))),
+        };
+        let w = info.width as usize;
+        let line = samples.len() / info.height as usize;
+        let rows = samples
+            .chunks_exact(line)
+            .map(|row| row[..channels * w]
+                .chunks_exact(channels)
+                .map(|p| if channels < 3 {
+                    color::Color { r: p[0], g: p[0], b: p[0] }
+                } else {
+                    color::Color { r: p[0], g: p[1], b: p[2] }
+                })
+                .collect())
+            .collect::<Vec<_>>();
+        Ok(Image::from(rows))
+    }
+
+    /// Divides premultiplied colors by row-major alpha values,
+    /// fully transparent pixels become black.
+    pub fn unpremultiplied(&self, alpha: &[f32]) -> Image<f32> {
+        assert_eq!(alpha.len(), self.w() * self.h(), "Alpha size mismatch");
+        let w = self.w();
+        Image(self.0
+            .iter()
+            .enumerate()
+            .map(|(i_row, row)| row
+                .iter()
+                .zip(&alpha[i_row * w..(i_row + 1) * w])
+                .map(|(c, a)| if *a > 0. {
+                    color::Color { r: c.r / a, g: c.g / a, b: c.b / a }
+                } else {
+                    color::Color { r: 0., g: 0., b: 0. }
+                })
+                .collect())
+            .collect())
+    }
+
+    /// Root mean square difference of the channels, images should have equal sizes.
+    pub fn rmse(&self, other: &Image<f32>) -> f64 {
+        assert_eq!((self.w(), self.h()), (other.w(), other.h()), "Image size mismatch");
+        let mut sum = 0.;
+        for (a, b) in self.rows().flatten().zip(other.rows().flatten()) {
+            sum += ((a.r - b.r) as f64).powi(2);
+            sum += ((a.g - b.g) as f64).powi(2);
+            sum += ((a.b - b.b) as f64).powi(2);
+        }
+        (sum / (3 * self.w() * self.h()) as f64).sqrt()
+    }
+}
+
+impl<T: Sample> Image<T> {
+    /// Writes 8 or 16-bit PNG with optional row-major alpha from `[0, 1]`.
+    pub fn write_png(&self, path: &Path, alpha: Option<&[f32]>) -> Result<(), Error> {
+        let file = fs::File::create(path)?;
+        self.write_png_to(io::BufWriter::new(file), alpha)
+    }
+
+    pub fn write_png_to(&self, w: impl io::Write, alpha: Option<&[f32]>) -> Result<(), Error> {
+        let mut encoder = png::Encoder::new(w, self.w() as u32, self.h() as u32);
+        encoder.set_color(if alpha.is_some() { png::ColorType::RGBA } else { png::ColorType::RGB });
+        encoder.set_depth(if T::BITS == 16 { png::BitDepth::Sixteen } else { png::BitDepth::Eight });
+        let data = self.samples_be(alpha);
+        Ok(encoder
+            .write_header()?
+            .write_image_data(&data)?)
+    }
+
+    /// Interleaved big-endian channels with alpha after the color if present.
+    fn samples_be(&self, alpha: Option<&[f32]>) -> Vec<u8> {
+        if let Some(alpha) = alpha {
+            assert_eq!(alpha.len(), self.w() * self.h(), "Alpha size mismatch");
+        }
+        let channels = if alpha.is_some() { 4 } else { 3 };
+        let mut res = Vec::with_capacity(channels * (T::BITS / 8) as usize * self.w() * self.h());
+        for (i, c) in self.0.iter().flatten().enumerate() {
+            c.r.push_be(&mut res);
+            c.g.push_be(&mut res);
+            c.b.push_be(&mut res);
+            if let Some(alpha) = alpha {
+                T::quantize(alpha[i] as f64).push_be(&mut res);
+            }
+        }
+        res
+    }
+}
+
+impl Image<u8> {
     pub fn linearized(&self) -> Vec<u8> {
         let mut res = Vec::with_capacity(3 * self.h() * self.w());
         for row in self.0.iter() {
@@ -71,15 +322,10 @@ impl Image {
         }
         res
     }
-
-    pub fn set_row(&mut self, index: usize, row: Vec<Color>) -> &mut Self {
-        self.0[index] = row;
-        self
-    }
 }
 
-impl From<Vec<Vec<Color>>> for Image {
-    fn from(data: Vec<Vec<Color>>) -> Self {
+impl<T: Num + Zero + Copy> From<Vec<Vec<color::Color<T>>>> for Image<T> {
+    fn from(data: Vec<Vec<color::Color<T>>>) -> Self {
         let img = Image(data);
         assert!(img.h() > 0);
         assert!(img.w() > 0);
@@ -87,16 +333,155 @@ impl From<Vec<Vec<Color>>> for Image {
     }
 }
 
-impl Index<(usize, usize)> for Image {
-    type Output = Color;
+impl<T: Num> Index<(usize, usize)> for Image<T> {
+    type Output = color::Color<T>;
 
     fn index(&self, (i_row, i_col): (usize, usize)) -> &Self::Output {
         &self.0[i_row][i_col]
     }
 }
 
-impl IndexMut<(usize, usize)> for Image {
+impl<T: Num> IndexMut<(usize, usize)> for Image<T> {
     fn index_mut(&mut self, (i_row, i_col): (usize, usize)) -> &mut Self::Output {
         &mut self.0[i_row][i_col]
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn exposure() {
+        let image = Image::from(vec![vec![color::Color { r: 0.25f32, g: 0.125, b: 2. }]]);
+        let exposed = |exposure| {
+            let c = image.tone_mapped(ToneMap::Clamp, exposure)[(0, 0)];
+            (c.r, c.g, c.b)
+        };
+        assert_eq!(exposed(0.), (0.25, 0.125, 1.));
+        assert_eq!(exposed(1.), (0.5, 0.25, 1.));
+        assert_eq!(exposed(-2.), (0.0625, 0.03125, 0.5));
+    }
+
+    #[test]
+    fn quantize() {
+        assert_eq!((u8::quantize(-0.5), u8::quantize(0.5), u8::quantize(2.)), (0, 128, 255));
+        assert_eq!((u16::quantize(0.), u16::quantize(0.5), u16::quantize(1.)), (0, 32768, 65535));
+    }
+
+    fn gradient() -> Image<f32> {
+        Image::from((0..3)
+            .map(|i_row| (0..5)
+                .map(|i_col| color::Color { r: i_col as f32 / 4., g: i_row as f32 / 2., b: 0.25 })
+                .collect())
+            .collect::<Vec<_>>())
+    }
+
+    /// Writes PNG and decodes it back, returning raw samples with their color type.
+    fn png_round_trip<T: Sample>(image: &Image<T>, alpha: Option<&[f32]>) -> (Image<f32>, png::ColorType, Vec<u8>) {
+        let mut bytes = vec![];
+        image.write_png_to(&mut bytes, alpha).unwrap();
+        let mut decoder = png::Decoder::new(bytes.as_slice());
+        decoder.set_transformations(png::Transformations::IDENTITY);
+        let (info, mut reader) = decoder.read_info().unwrap();
+        let mut data = vec![0; info.buffer_size()];
+        reader.next_frame(&mut data).unwrap();
+        assert_eq!(data, image.samples_be(alpha));
+        (Image::read_png_from(bytes.as_slice()).unwrap(), info.color_type, data)
+    }
+
+    #[test]
+    fn png_8_bit() {
+        let image = gradient().to_ldr(Encoding::Linear);
+        let (read, color_type, _) = png_round_trip(&image, None);
+        assert_eq!(color_type, png::ColorType::RGB);
+        assert!(read.rmse(&gradient()) < 0.5 / 255.);
+
+        let alpha: Vec<f32> = (0..15).map(|i| i as f32 / 14.).collect();
+        let (read, color_type, data) = png_round_trip(&image, Some(&alpha));
+        assert_eq!(color_type, png::ColorType::RGBA);
+        assert!(read.rmse(&gradient()) < 0.5 / 255.);
+        assert_eq!((data[3], data[7], data[59]), (0, 18, 255));
+    }
+
+    #[test]
+    fn png_16_bit() {
+        let image = gradient().to_ldr16(Encoding::Linear);
+        let (read, color_type, data) = png_round_trip(&image, None);
+        assert_eq!(color_type, png::ColorType::RGB);
+        assert_eq!(data.len(), 2 * 3 * 15);
+        assert!(read.rmse(&gradient()) < 0.5 / 65535.);
+
+        let alpha = vec![0.5; 15];
+        let (read, color_type, data) = png_round_trip(&image, Some(&alpha));
+        assert_eq!(color_type, png::ColorType::RGBA);
+        assert!(read.rmse(&gradient()) < 0.5 / 65535.);
+        assert_eq!(&data[6..8], &[0x80, 0]);
+    }
+
+    fn encode_png(w: u32, color_type: png::ColorType, depth: png::BitDepth, data: &[u8]) -> Vec<u8> {
+        let mut bytes = vec![];
+        let mut encoder = png::Encoder::new(&mut bytes, w, 2);
+        encoder.set_color(color_type);
+        encoder.set_depth(depth);
+        if color_type == png::ColorType::Indexed {
+            encoder.set_palette(vec![0, 0, 0, 255, 0, 0, 0, 51, 255]);
+            encoder.set_trns(vec![255, 0]);
+        }
+        encoder.write_header().unwrap().write_image_data(data).unwrap();
+        bytes
+    }
+
+    fn channels(image: &Image<f32>) -> Vec<(f32, f32, f32)> {
+        image.rows().flatten().map(|c| (c.r, c.g, c.b)).collect()
+    }
+
+    #[test]
+    fn read_png() {
+        use png::{BitDepth::*, ColorType::*};
+        let read = |w, color_type, depth, data: &[u8]| {
+            channels(&Image::read_png_from(encode_png(w, color_type, depth, data).as_slice()).unwrap())
+        };
+        let rgb = vec![(1., 0., 0.2), (0., 1., 0.6)];
+        assert_eq!(read(1, RGB, Eight, &[255, 0, 51, 0, 255, 153]), rgb);
+        assert_eq!(read(1, RGBA, Eight, &[255, 0, 51, 0, 0, 255, 153, 255]), rgb);
+        assert_eq!(read(1, RGB, Sixteen, &[255, 255, 0, 0, 51, 51, 0, 0, 255, 255, 153, 153]), rgb);
+        let rgba16 = [255, 255, 0, 0, 51, 51, 0, 0, 0, 0, 255, 255, 153, 153, 1, 1];
+        assert_eq!(read(1, RGBA, Sixteen, &rgba16), rgb);
+
+        let gray = vec![(0.2, 0.2, 0.2), (1., 1., 1.), (0., 0., 0.), (0.6, 0.6, 0.6)];
+        assert_eq!(read(2, Grayscale, Eight, &[51, 255, 0, 153]), gray);
+        assert_eq!(read(2, Grayscale, Sixteen, &[51, 51, 255, 255, 0, 0, 153, 153]), gray);
+        assert_eq!(read(2, GrayscaleAlpha, Eight, &[51, 0, 255, 9, 0, 255, 153, 255]), gray);
+        // Four 1-bit gray pixels per row are expanded to 8 bits.
+        let bits = read(4, Grayscale, One, &[0x50, 0xa0]);
+        assert_eq!(bits.iter().map(|c| c.0).collect::<Vec<_>>(), vec![0., 1., 0., 1., 1., 0., 1., 0.]);
+        // Palette entries with transparency become RGBA.
+        assert_eq!(read(2, Indexed, Eight, &[1, 2, 0, 1]), vec![(1., 0., 0.), (0., 0.2, 1.), (0., 0., 0.), (1., 0., 0.)]);
+    }
+
+    #[test]
+    fn read_png_truncated() {
+        let mut bytes = vec![];
+        gradient().to_ldr(Encoding::Srgb).write_png_to(&mut bytes, None).unwrap();
+        for len in [bytes.len() / 2, bytes.len() - 20, 20] {
+            let res = Image::read_png_from(&bytes[..len]);
+            assert!(matches!(res, Err(Error::Corrupt(_))), "{} bytes: {:?}", len, res);
+        }
+    }
+
+    #[test]
+    fn unpremultiplied() {
+        let image = Image::from(vec![vec![
+            color::Color { r: 0.25f32, g: 0.5, b: 0. },
+            color::Color { r: 0., g: 0., b: 0. },
+            color::Color { r: 0.1, g: 0.2, b: 0.3 },
+        ]]);
+        let image = image.unpremultiplied(&[0.5, 0., 0.]);
+        assert_eq!((image[(0, 0)].r, image[(0, 0)].g, image[(0, 0)].b), (0.5, 1., 0.));
+        for i_col in 1..3 {
+            let c = image[(0, i_col)];
+            assert_eq!((c.r, c.g, c.b), (0., 0., 0.));
+        }
+    }
+}
@@ -0,0 +1,158 @@
+use std::{fs, io};
+use std::io::{BufRead, Write};
+use std::path::Path;
+
+use crate::{check_size, Error, Image};
+
+/// Portable float maps, rows are stored from bottom to top.
+impl Image<f32> {
+    pub fn read_pfm(path: &Path) -> Result<Image<f32>, Error> {
+        let file = fs::File::open(path).map_err(Error::ReadIO)?;
+        Image::read_pfm_from(&mut io::BufReader::new(file))
+    }
+
+    pub fn read_pfm_from(r: &mut impl BufRead) -> Result<Image<f32>, Error> {
+        let channels = match token(r)?.as_str() {
+            "PF" => 3,
+            "Pf" => 1,
+            _ => return Err(Error::Corrupt("missing PFM signature".to_string())),
+        };
+        let w = token(r)?.parse::<usize>();
+        let h = token(r)?.parse::<usize>();
+        let (w, h) = match (w, h) {
+            (Ok(w), Ok(h)) if w > 0 && h > 0 => (w, h),
+            _ => return Err(Error::Corrupt("invalid resolution".to_string())),
+        };
+        let scale: f32 = token(r)?
+            .parse()
+            .map_err(|_| Error::Corrupt("invalid scale".to_string()))?;
+        let little_endian = scale < 0.;
+
+        let mut data = vec![0; 4 * channels * check_size(w, h)?];
+        r.read_exact(&mut data).map_err(|e| match e.kind() {
+            io::ErrorKind::UnexpectedEof => Error::Corrupt("unexpected end of pixel data".to_string()),
+            _ => Error::ReadIO(e),
+        })?;
+        let values: Vec<f32> = data
+            .chunks_exact(4)
+            .map(|b| {
+                let b = [b[0], b[1], b[2], b[3]];
+                if little_endian { f32::from_le_bytes(b) } else { f32::from_be_bytes(b) }
+            })
+            .collect();
+        let rows: Vec<Vec<_>> = values
+            .chunks_exact(channels * w)
+            .rev()
+            .map(|row| row
+                .chunks_exact(channels)
+                .map(|p| match p {
+                    [r, g, b] => color::Color { r: *r, g: *g, b: *b },
+                    _ => color::Color { r: p[0], g: p[0], b: p[0] },
+                })
+                .collect())
+            .collect();
+        Ok(Image::from(rows))
+    }
+
+    pub fn write_pfm(&self, path: &Path) -> Result<(), Error> {
+        let file = fs::File::create(path)?;
+        let mut writer = io::BufWriter::new(file);
+        self.write_pfm_to(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn write_pfm_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(w, "PF\n{} {}\n-1.0\n", self.w(), self.h())?;
+        let rows: Vec<_> = self.rows().collect();
+        for row in rows.into_iter().rev() {
+            for c in row {
+                w.write_all(&c.r.to_le_bytes())?;
+                w.write_all(&c.g.to_le_bytes())?;
+                w.write_all(&c.b.to_le_bytes())?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Header token along with the single whitespace character following it.
+fn token(r: &mut impl BufRead) -> Result<String, Error> {
+    let mut token = String::new();
+    loop {
+        let mut b = [0];
+        if r.read(&mut b).map_err(Error::ReadIO)? == 0 {
+            return Err(Error::Corrupt("unexpected end of header".to_string()));
+        }
+        let c = b[0] as char;
+        if c.is_ascii_whitespace() {
+            if !token.is_empty() {
+                return Ok(token);
+            }
+        } else {
+            token.push(c);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bits(image: &Image<f32>) -> Vec<[u32; 3]> {
+        image.rows().flatten().map(|c| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()]).collect()
+    }
+
+    #[test]
+    fn write_read() {
+        let rows: Vec<Vec<_>> = (0..3)
+            .map(|y| (0..4)
+                .map(|x| color::Color { r: x as f32 * 1e10, g: -(y as f32) / 3., b: f32::MIN_POSITIVE })
+                .collect())
+            .collect();
+        let original = Image::from(rows);
+        let mut bytes = vec![];
+        original.write_pfm_to(&mut bytes).unwrap();
+        let read = Image::read_pfm_from(&mut bytes.as_slice()).unwrap();
+        assert_eq!(bits(&read), bits(&original));
+    }
+
+    #[test]
+    fn byte_order_and_rows() {
+        // Negative scale stands for little endian, the first stored row is the bottom one.
+        let mut le = b"PF\n1 2\n-1.0\n".to_vec();
+        let mut be = b"PF\n1 2\n1.0\n".to_vec();
+        for v in [1f32, 2., 3., 4., 5., 6.] {
+            le.extend_from_slice(&v.to_le_bytes());
+            be.extend_from_slice(&v.to_be_bytes());
+        }
+        for bytes in [le, be] {
+            let image = Image::read_pfm_from(&mut bytes.as_slice()).unwrap();
+            let pixels: Vec<_> = image.rows().flatten().map(|c| (c.r, c.g, c.b)).collect();
+            assert_eq!(pixels, [(4., 5., 6.), (1., 2., 3.)]);
+        }
+    }
+
+    #[test]
+    fn grayscale() {
+        let mut bytes = b"Pf 2 1 -1\n".to_vec();
+        bytes.extend_from_slice(&0.5f32.to_le_bytes());
+        bytes.extend_from_slice(&2f32.to_le_bytes());
+        let image = Image::read_pfm_from(&mut bytes.as_slice()).unwrap();
+        let pixels: Vec<_> = image.rows().flatten().map(|c| (c.r, c.g, c.b)).collect();
+        assert_eq!(pixels, [(0.5, 0.5, 0.5), (2., 2., 2.)]);
+    }
+
+    #[test]
+    fn corrupt() {
+        let truncated = b"PF\n2 2\n-1.0\n\0\0\0\0";
+        assert!(matches!(Image::read_pfm_from(&mut &truncated[..]), Err(Error::Corrupt(_))));
+        assert!(matches!(Image::read_pfm_from(&mut &b"PF\n0 2\n-1.0\n"[..]), Err(Error::Corrupt(_))));
+        assert!(matches!(Image::read_pfm_from(&mut &b"PF\n1 1\nscale\n"[..]), Err(Error::Corrupt(_))));
+    }
+
+    #[test]
+    fn oversized() {
+        let header = b"PF\n4000000000 4000000000\n-1.0\n";
+        assert!(matches!(Image::read_pfm_from(&mut &header[..]), Err(Error::Unsupported(_))));
+    }
+}
@@ -0,0 +1,83 @@
+use std::{fs, io};
+use std::io::Write;
+use std::path::Path;
+
+use crate::{Error, Image, Sample};
+
+/// Netpbm images with 8 or 16-bit channels.
+impl<T: Sample> Image<T> {
+    pub fn write_ppm(&self, path: &Path) -> Result<(), Error> {
+        let file = fs::File::create(path)?;
+        let mut writer = io::BufWriter::new(file);
+        self.write_ppm_to(&mut writer)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn write_ppm_to(&self, w: &mut impl Write) -> io::Result<()> {
+        write!(w, "P6\n{} {}\n{}\n", self.w(), self.h(), max_value::<T>())?;
+        w.write_all(&self.samples_be(None))
+    }
+
+    /// Writes PAM with optional row-major alpha from `[0, 1]`.
+    pub fn write_pam(&self, path: &Path, alpha: Option<&[f32]>) -> Result<(), Error> {
+        let file = fs::File::create(path)?;
+        let mut writer = io::BufWriter::new(file);
+        self.write_pam_to(&mut writer, alpha)?;
+        Ok(writer.flush()?)
+    }
+
+    pub fn write_pam_to(&self, w: &mut impl Write, alpha: Option<&[f32]>) -> io::Result<()> {
+        let (depth, tuple_type) = if alpha.is_some() { (4, "RGB_ALPHA") } else { (3, "RGB") };
+        write!(
+            w, "P7\nWIDTH {}\nHEIGHT {}\nDEPTH {}\nMAXVAL {}\nTUPLTYPE {}\nENDHDR\n",
+            self.w(), self.h(), depth, max_value::<T>(), tuple_type,
+        )?;
+        w.write_all(&self.samples_be(alpha))
+    }
+}
+
+fn max_value<T: Sample>() -> u32 {
+    (1 << T::BITS) - 1
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image() -> Image<u16> {
+        Image::from(vec![vec![
+            color::Color { r: 0x0102, g: 0x0304, b: 0xffff },
+            color::Color { r: 0, g: 1, b: 0x8000 },
+        ]])
+    }
+
+    #[test]
+    fn ppm() {
+        let mut bytes = vec![];
+        image().write_ppm_to(&mut bytes).unwrap();
+        let mut expected = b"P6\n2 1\n65535\n".to_vec();
+        expected.extend_from_slice(&[1, 2, 3, 4, 0xff, 0xff, 0, 0, 0, 1, 0x80, 0]);
+        assert_eq!(bytes, expected);
+
+        let mut bytes = vec![];
+        image().map(|c| color::Color { r: (c.r >> 8) as u8, g: (c.g >> 8) as u8, b: (c.b >> 8) as u8 })
+            .write_ppm_to(&mut bytes)
+            .unwrap();
+        assert_eq!(bytes, b"P6\n2 1\n255\n\x01\x03\xff\x00\x00\x80");
+    }
+
+    #[test]
+    fn pam() {
+        let mut bytes = vec![];
+        image().write_pam_to(&mut bytes, Some(&[1., 0.5])).unwrap();
+        let mut expected = b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 4\nMAXVAL 65535\nTUPLTYPE RGB_ALPHA\nENDHDR\n".to_vec();
+        expected.extend_from_slice(&[1, 2, 3, 4, 0xff, 0xff, 0xff, 0xff, 0, 0, 0, 1, 0x80, 0, 0x80, 0]);
+        assert_eq!(bytes, expected);
+
+        let mut bytes = vec![];
+        image().write_pam_to(&mut bytes, None).unwrap();
+        let header = b"P7\nWIDTH 2\nHEIGHT 1\nDEPTH 3\nMAXVAL 65535\nTUPLTYPE RGB\nENDHDR\n";
+        assert_eq!(&bytes[..header.len()], &header[..]);
+        assert_eq!(bytes.len(), header.len() + 12);
+    }
+}
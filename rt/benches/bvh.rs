@@ -0,0 +1,60 @@
+extern crate criterion;
+extern crate color;
+extern crate rt;
+
+use std::num::NonZeroUsize;
+
+use criterion::{BenchmarkId, Criterion, criterion_group, criterion_main};
+
+use color::Color;
+use rt::{Accel, Camera, Lambertian, NormVector, Positive, Render, Scene, Sphere, Vector};
+
+/// Grid of `n * n` small spheres in front of the camera.
+fn scene(n: usize, accel: Accel) -> Scene {
+    let mut scene = Scene::new()
+        .width(NonZeroUsize::new(32).unwrap())
+        .height(NonZeroUsize::new(18).unwrap())
+        .cam(Camera {
+            pos: Vector::new(0., 0., 1.),
+            up: NormVector::new(0., 1., 0.),
+            to: Vector::new(0., 0., -1.),
+            vfov: Positive::new(60.).unwrap(),
+            aspect_ratio: Positive::new(16. / 9.).unwrap(),
+        })
+        .accel(accel)
+        .background_getter(Box::new(|_| Color { r: 1., g: 1., b: 1. }));
+    let step = 20. / n as f64;
+    for i in 0..n {
+        for j in 0..n {
+            scene = scene.add_sphere(
+                Sphere::new()
+                    .center(Vector::new(
+                        -10. + step * i as f64,
+                        -10. + step * j as f64,
+                        -10. - (i + j) as f64 % 3.,
+                    ))
+                    .radius(Positive::new(0.4 * step).unwrap())
+                    .lambertian(Lambertian::new(Color { r: 200u8, g: 200, b: 200 }))
+                    .build()
+            );
+        }
+    }
+    scene.build()
+}
+
+fn touch_all(c: &mut Criterion) {
+    let mut group = c.benchmark_group("touch_all");
+    group.sample_size(10);
+    for n in [10, 30, 60].iter() {
+        for (name, accel) in [("linear", Accel::Linear), ("bvh", Accel::Bvh)].iter() {
+            let scene = scene(*n, *accel);
+            group.bench_with_input(BenchmarkId::new(*name, n * n), &scene, |b, scene| {
+                b.iter(|| Render::new(scene).diffuse_depth(4).render())
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group!(benches, touch_all);
+criterion_main!(benches);
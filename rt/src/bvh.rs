@@ -0,0 +1,307 @@
+use crate::objs::{TouchBox, Touching};
+use crate::ray::Ray;
+use crate::utils::Aabb;
+use crate::Vector;
+
+const BINS: usize = 12;
+const MAX_LEAF_SIZE: usize = 8;
+const TRAVERSAL_COST: f64 = 0.125;
+
+/// Structure used to search for the closest object touched by a ray.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Accel {
+    /// Check every object of the scene for every ray.
+    Linear,
+    /// Bounding volume hierarchy split by the surface area heuristic.
+    #[default]
+    Bvh,
+}
+
+pub(crate) struct Bvh {
+    objs: Vec<TouchBox>,
+    nodes: Vec<Node>,
+}
+
+struct Node {
+    bounds: Aabb,
+    kind: NodeKind,
+}
+
+enum NodeKind {
+    Leaf { first: usize, count: usize },
+    /// First child goes right after its parent.
+    Inner { axis: usize, second: usize },
+}
+
+struct Prim {
+    index: usize,
+    bounds: Aabb,
+    centroid: Vector,
+}
+
+impl Bvh {
+    pub(crate) fn new(objs: Vec<TouchBox>, accel: Accel) -> Self {
+        if objs.is_empty() {
+            return Bvh { objs, nodes: vec![] };
+        }
+        let mut prims: Vec<_> = objs
+            .iter()
+            .enumerate()
+            .map(|(index, obj)| {
+                let bounds = obj.bounds();
+                let centroid = bounds.centroid();
+                Prim { index, bounds, centroid }
+            })
+            .collect();
+        let mut nodes = vec![];
+        match accel {
+            Accel::Linear => {
+                let bounds = bounds_of(&prims);
+                nodes.push(Node { bounds, kind: NodeKind::Leaf { first: 0, count: prims.len() } });
+            }
+            Accel::Bvh => {
+                build_node(&mut nodes, &mut prims, 0);
+            }
+        }
+        let mut objs: Vec<_> = objs.into_iter().map(Some).collect();
+        let objs = prims
+            .iter()
+            .map(|prim| objs[prim.index].take().unwrap())
+            .collect();
+        Bvh { objs, nodes }
+    }
+
+    pub(crate) fn bounds(&self) -> Aabb {
+        self.nodes
+            .first()
+            .map_or_else(Aabb::empty, |node| node.bounds.clone())
+    }
+
+    /// Closest touching with `t` inside of `(t_min, t_max)`.
+    pub(crate) fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching> {
+        if self.nodes.is_empty() {
+            return None;
+        }
+        let inv_dir = r.dir.map(|x| 1. / x);
+        let mut t_max = t_max;
+        let mut res = None;
+        let mut stack = Vec::with_capacity(64);
+        stack.push(0);
+        while let Some(i) = stack.pop() {
+            let node = &self.nodes[i];
+            if !node.bounds.touch(&r.orig, &inv_dir, t_min, t_max) {
+                continue;
+            }
+            match node.kind {
+                NodeKind::Leaf { first, count } => {
+                    for obj in &self.objs[first..first + count] {
+                        if let Some(touching) = obj.touch(r, t_min, t_max) {
+                            t_max = touching.t.get();
+                            res = Some(touching);
+                        }
+                    }
+                }
+                NodeKind::Inner { axis, second } => {
+                    // Visit the nearest child first to shrink `t_max` early.
+                    if r.dir[axis] < 0. {
+                        stack.push(i + 1);
+                        stack.push(second);
+                    } else {
+                        stack.push(second);
+                        stack.push(i + 1);
+                    }
+                }
+            }
+        }
+        res
+    }
+}
+
+fn bounds_of(prims: &[Prim]) -> Aabb {
+    prims.iter().fold(Aabb::empty(), |b, prim| b.union(&prim.bounds))
+}
+
+fn build_node(nodes: &mut Vec<Node>, prims: &mut [Prim], first: usize) -> usize {
+    let index = nodes.len();
+    let bounds = bounds_of(prims);
+    let count = prims.len();
+    let split = if count > 1 { split(prims, &bounds) } else { None };
+    nodes.push(Node { bounds, kind: NodeKind::Leaf { first, count } });
+    if let Some((axis, mid)) = split {
+        let (left, right) = prims.split_at_mut(mid);
+        build_node(nodes, left, first);
+        let second = build_node(nodes, right, first + mid);
+        nodes[index].kind = NodeKind::Inner { axis, second };
+    }
+    index
+}
+
+/// Partitions `prims` by the cheapest binned SAH split, returns axis and partition point.
+fn split(prims: &mut [Prim], bounds: &Aabb) -> Option<(usize, usize)> {
+    let centroids = prims
+        .iter()
+        .fold(Aabb::empty(), |b, prim| b.union(&Aabb::new(prim.centroid, prim.centroid)));
+    let bin_of = |prim: &Prim, axis: usize| {
+        let lo = centroids.min[axis];
+        let extent = centroids.max[axis] - lo;
+        let bin = (BINS as f64 * (prim.centroid[axis] - lo) / extent) as usize;
+        bin.min(BINS - 1)
+    };
+
+    let mut best: Option<(f64, usize, usize)> = None;
+    for axis in 0..3 {
+        if centroids.max[axis] - centroids.min[axis] <= 0. {
+            continue;
+        }
+        let mut counts = [0; BINS];
+        let mut bins: Vec<_> = (0..BINS).map(|_| Aabb::empty()).collect();
+        for prim in prims.iter() {
+            let bin = bin_of(prim, axis);
+            counts[bin] += 1;
+            bins[bin] = bins[bin].union(&prim.bounds);
+        }
+        for split in 1..BINS {
+            let (left, right) = bins.split_at(split);
+            let n_left: usize = counts[..split].iter().sum();
+            let n_right: usize = counts[split..].iter().sum();
+            if n_left == 0 || n_right == 0 {
+                continue;
+            }
+            let a_left = left.iter().fold(Aabb::empty(), |b, x| b.union(x)).surface_area();
+            let a_right = right.iter().fold(Aabb::empty(), |b, x| b.union(x)).surface_area();
+            let cost = TRAVERSAL_COST
+                + (a_left * n_left as f64 + a_right * n_right as f64) / bounds.surface_area();
+            if best.is_none_or(|(c, _, _)| cost < c) {
+                best = Some((cost, axis, split));
+            }
+        }
+    }
+
+    let (cost, axis, split) = best?;
+    if cost >= prims.len() as f64 && prims.len() <= MAX_LEAF_SIZE {
+        return None;
+    }
+    let mut mid = 0;
+    for i in 0..prims.len() {
+        if bin_of(&prims[i], axis) < split {
+            prims.swap(i, mid);
+            mid += 1;
+        }
+    }
+    Some((axis, mid))
+}
+
+#[cfg(test)]
+mod tests {
+    use rand::{Rng, SeedableRng};
+    use rand_chacha::ChaCha8Rng;
+
+    use color::Color;
+
+    use super::*;
+    use crate::utils::{NormVector, Positive};
+    use crate::{DiffuseLight, Lambertian, Sphere, Triangle};
+
+    /// Grid shifted by `i / 1000` along every axis.
+    fn grid(rng: &mut ChaCha8Rng, n: i32, i: usize) -> Vector {
+        let mut coord = || rng.gen_range(-n, n) as f64 + i as f64 / 1000.;
+        Vector::new(coord(), coord(), coord())
+    }
+
+    /// Spheres and triangles including flat triangles with zero-extent bounds
+    /// and coincident objects with identical centroids, emitting their index.
+    /// Each is on its own grid, so no two are touched at the same point.
+    fn objs(rng: &mut ChaCha8Rng) -> Vec<TouchBox> {
+        let mut objs: Vec<TouchBox> = vec![];
+        for i in 0..200 {
+            let material = || DiffuseLight { emit: Color { r: i as f64, g: 0., b: 0. } };
+            let mut point = || grid(rng, 8, i);
+            let p = point();
+            objs.push(match i % 4 {
+                0 => Box::new(Sphere::new()
+                    .center(p)
+                    .radius(Positive::new(0.5 + (i % 3) as f64).unwrap())
+                    .diffuse_light(material())
+                    .build()),
+                1 => Box::new(Triangle::new()
+                    .vertices(p, point(), point())
+                    .diffuse_light(material())
+                    .build()),
+                // Parallel to the XY plane.
+                2 => Box::new(Triangle::new()
+                    .vertices(p, p + Vector::new(2., 0., 0.), p + Vector::new(0., 3., 0.))
+                    .diffuse_light(material())
+                    .build()),
+                _ => Box::new(Sphere::new()
+                    .center(Vector::zeros())
+                    .radius(Positive::new(1. + i as f64 / 100.).unwrap())
+                    .diffuse_light(material())
+                    .build()),
+            });
+        }
+        objs
+    }
+
+    fn id(touching: &Touching) -> f64 {
+        touching.material.emitted(touching).r
+    }
+
+    fn ray(rng: &mut ChaCha8Rng, i: usize) -> Ray {
+        let axes = [Vector::x(), Vector::y(), Vector::z()];
+        if i.is_multiple_of(2) {
+            // Axis-parallel from grid points, running along faces of bounding boxes.
+            let i = rng.gen_range(0, 200);
+            let orig = grid(rng, 10, i);
+            let sign = if rng.gen() { 1. } else { -1. };
+            Ray { orig, dir: NormVector::from(sign * axes[rng.gen_range(0, 3)]) }
+        } else {
+            let mut coord = || rng.gen_range(-12., 12.);
+            let orig = Vector::new(coord(), coord(), coord());
+            let dir = Vector::new(coord(), coord(), coord());
+            Ray { orig, dir: NormVector::from(dir) }
+        }
+    }
+
+    #[test]
+    fn same_as_linear() {
+        let mut rng = ChaCha8Rng::seed_from_u64(1);
+        let bvh = Bvh::new(objs(&mut ChaCha8Rng::seed_from_u64(2)), Accel::Bvh);
+        let linear = Bvh::new(objs(&mut ChaCha8Rng::seed_from_u64(2)), Accel::Linear);
+        assert!(bvh.nodes.len() > 1);
+        let mut hits = 0;
+        for i in 0..4000 {
+            let r = ray(&mut rng, i);
+            match (bvh.touch(&r, 0.001, f64::MAX), linear.touch(&r, 0.001, f64::MAX)) {
+                (Some(a), Some(b)) => {
+                    assert_eq!(a.t.get(), b.t.get());
+                    assert_eq!(id(&a), id(&b));
+                    hits += 1;
+                }
+                (None, None) => {}
+                (a, b) => panic!("ray {:?}: bvh hit {}, linear hit {}", r, a.is_some(), b.is_some()),
+            }
+        }
+        assert!(hits > 500);
+    }
+
+    #[test]
+    fn degenerate() {
+        let p = Vector::new(1., 2., 3.);
+        let material = || Lambertian::new(Color { r: 1., g: 1., b: 1. });
+        // Spheres sharing the centroid and triangles collapsed into it.
+        let objs = (0..20usize)
+            .map(|i| -> TouchBox {
+                if i.is_multiple_of(2) {
+                    Box::new(Sphere::new().center(p).radius(Positive::new(1.).unwrap()).lambertian(material()).build())
+                } else {
+                    Box::new(Triangle::new().vertices(p, p, p).lambertian(material()).build())
+                }
+            })
+            .collect();
+        let bvh = Bvh::new(objs, Accel::Bvh);
+        let r = Ray { orig: Vector::new(1., 2., -10.), dir: NormVector::new(0., 0., 1.) };
+        assert_eq!(bvh.touch(&r, 0.001, f64::MAX).unwrap().t.get(), 12.);
+        let empty = Bvh::new(vec![], Accel::Bvh);
+        assert!(empty.touch(&r, 0.001, f64::MAX).is_none());
+    }
+}
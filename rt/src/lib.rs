@@ -5,21 +5,43 @@ extern crate image;
 extern crate nalgebra as na;
 extern crate num_traits;
 extern crate rand;
+extern crate rand_chacha;
 extern crate rayon;
+extern crate serde;
+extern crate toml;
+
+pub use color::{Encoding, ToneMap};
 
 pub use crate::{
-    objs::{Lambertian, Metal, Sphere, SphereBuilder},
+    bvh::Accel,
+    lights::{DirectionalLight, PointLight, SpotLight},
+    obj::{load_obj, ObjError, ObjErrorKind},
+    objs::{
+        Dielectric, DiffuseLight, Lambertian, Metal, Sphere, SphereBuilder,
+        Triangle, TriangleBuilder, TriangleMesh, TriangleMeshBuilder,
+    },
     ray::Ray,
-    render::Logger,
+    sampler::Sampler,
+    render::{Adaptive, Aovs, Logger, PassCallback, Sampled, TileCallback},
     render::Render,
     scene::{Camera, Scene, SceneBuilder},
+    scene_file::{load_scene, SceneFileError},
+    tiles::{Tile, TileOrder},
+    textures::{Checker, ImageTexture, Noise, NoisePattern, Perlin, Texture, TextureArc},
     utils::*,
 };
 
+mod bvh;
+mod lights;
+mod obj;
 mod scene;
+mod scene_file;
 mod objs;
 mod render;
 mod ray;
+mod sampler;
+mod textures;
+mod tiles;
 mod utils;
 
 pub type VFloat = f64;
@@ -0,0 +1,149 @@
+use color::Color;
+
+use crate::utils::NormVector;
+use crate::Vector;
+
+/// Light from an infinitely small point, radiance `1.0` of a white surface
+/// facing the light from the unit distance needs intensity `PI`.
+pub struct PointLight {
+    pub position: Vector,
+    pub intensity: Color<f64>,
+}
+
+/// Point light shining inside of the cone around `direction`.
+pub struct SpotLight {
+    pub position: Vector,
+    pub direction: NormVector,
+    pub intensity: Color<f64>,
+    /// Angle in degrees between the axis and the edge of the cone.
+    pub cone_angle: f64,
+    /// Angle in degrees where the intensity starts fading to zero at the edge of the cone.
+    pub falloff_angle: f64,
+}
+
+/// Light coming from the infinitely far source along `direction`, like sunlight.
+pub struct DirectionalLight {
+    pub direction: NormVector,
+    /// Irradiance of surfaces perpendicular to the direction.
+    pub irradiance: Color<f64>,
+}
+
+/// Light arriving to a point from a delta light.
+pub(crate) struct Illumination {
+    /// Direction towards the light.
+    pub(crate) dir: NormVector,
+    /// Distance to the light, infinite for directional lights.
+    pub(crate) dist: f64,
+    /// Irradiance of a surface perpendicular to `dir`.
+    pub(crate) irradiance: Color<f64>,
+}
+
+/// Light which can not be touched by rays, so it is reachable by shadow rays only.
+pub(crate) trait DeltaLight {
+    /// `None` if the light does not reach the point.
+    fn illuminate(&self, p: &Vector) -> Option<Illumination>;
+}
+
+pub(crate) type DeltaLightBox = Box<dyn DeltaLight + Send + Sync + 'static>;
+
+impl DeltaLight for PointLight {
+    fn illuminate(&self, p: &Vector) -> Option<Illumination> {
+        let to = self.position - p;
+        let dist2 = to.norm_squared();
+        if dist2 == 0. {
+            return None;
+        }
+        Some(Illumination {
+            dir: NormVector::from(to),
+            dist: dist2.sqrt(),
+            irradiance: (1. / dist2) * self.intensity,
+        })
+    }
+}
+
+impl DeltaLight for SpotLight {
+    fn illuminate(&self, p: &Vector) -> Option<Illumination> {
+        let Illumination { dir, dist, irradiance } = PointLight {
+            position: self.position,
+            intensity: self.intensity,
+        }.illuminate(p)?;
+        let cos = -dir.dot(&self.direction);
+        let cos_cone = self.cone_angle.to_radians().cos();
+        let cos_falloff = self.falloff_angle.min(self.cone_angle).to_radians().cos();
+        if cos <= cos_cone {
+            return None;
+        }
+        let falloff = if cos >= cos_falloff {
+            1.
+        } else {
+            let x = (cos - cos_cone) / (cos_falloff - cos_cone);
+            x * x * (3. - 2. * x)
+        };
+        Some(Illumination { dir, dist, irradiance: falloff * irradiance })
+    }
+}
+
+impl DeltaLight for DirectionalLight {
+    fn illuminate(&self, _: &Vector) -> Option<Illumination> {
+        Some(Illumination {
+            dir: NormVector::from_unchecked(-self.direction.get()),
+            dist: f64::INFINITY,
+            irradiance: self.irradiance,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+
+    fn white() -> Color<f64> {
+        Color { r: 1., g: 1., b: 1. }
+    }
+
+    #[test]
+    fn point_inverse_square() {
+        let light = PointLight { position: Vector::new(0., 2., 0.), intensity: white() };
+        let near = light.illuminate(&Vector::new(0., 1., 0.)).unwrap();
+        let far = light.illuminate(&Vector::new(0., 0., 0.)).unwrap();
+        assert_abs_diff_eq!(near.dist, 1.);
+        assert_abs_diff_eq!(far.dist, 2.);
+        assert_abs_diff_eq!(far.dir.y, 1.);
+        assert_abs_diff_eq!(near.irradiance.r / far.irradiance.r, 4., epsilon = 1e-12);
+        assert!(light.illuminate(&Vector::new(0., 2., 0.)).is_none());
+    }
+
+    #[test]
+    fn spot_cone_falloff() {
+        let light = SpotLight {
+            position: Vector::new(0., 1., 0.),
+            direction: NormVector::new(0., -1., 0.),
+            intensity: white(),
+            cone_angle: 45.,
+            falloff_angle: 30.,
+        };
+        // Points on the floor one unit below at the angle in degrees from the axis.
+        let irradiance = |angle: f64| {
+            let p = Vector::new(angle.to_radians().tan(), 0., 0.);
+            light.illuminate(&p).map_or(0., |i| i.irradiance.r * i.dist * i.dist)
+        };
+        assert_abs_diff_eq!(irradiance(0.), 1.);
+        assert_abs_diff_eq!(irradiance(29.), 1.);
+        let fading: Vec<_> = (31..45).map(|a| irradiance(a as f64)).collect();
+        assert!(fading.windows(2).all(|w| w[0] > w[1]));
+        assert!(fading.iter().all(|i| (0. ..1.).contains(i)));
+        assert_eq!(irradiance(46.), 0.);
+        assert!(light.illuminate(&Vector::new(0., 2., 0.)).is_none());
+    }
+
+    #[test]
+    fn directional() {
+        let light = DirectionalLight { direction: NormVector::new(0., -1., 0.), irradiance: white() };
+        let i = light.illuminate(&Vector::new(5., -3., 1.)).unwrap();
+        assert_abs_diff_eq!(i.dir.y, 1.);
+        assert_eq!(i.dist, f64::INFINITY);
+        assert_eq!(i.irradiance.r, 1.);
+    }
+}
@@ -0,0 +1,326 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+use std::str::SplitWhitespace;
+use std::sync::Arc;
+
+use crate::objs::{Lambertian, MaterialArc, TriangleMesh};
+use crate::utils::NormVector;
+use crate::Vector;
+
+mod mtl;
+
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: PathBuf,
+    /// Line number starting from 1, absent for errors not bound to a line.
+    pub line: Option<usize>,
+    pub kind: ObjErrorKind,
+}
+
+#[derive(Debug)]
+pub enum ObjErrorKind {
+    IO(io::Error),
+    MissingValue(&'static str),
+    InvalidNumber(String),
+    InvalidIndex(String),
+    ZeroNormal,
+    FaceTooSmall,
+    UnknownMaterial(String),
+    Image(image::Error),
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.path.display())?;
+        if let Some(line) = self.line {
+            write!(f, ":{}", line)?;
+        }
+        match &self.kind {
+            ObjErrorKind::IO(e) => write!(f, ": {}", e),
+            ObjErrorKind::MissingValue(what) => write!(f, ": missing {}", what),
+            ObjErrorKind::InvalidNumber(s) => write!(f, ": invalid number '{}'", s),
+            ObjErrorKind::InvalidIndex(s) => write!(f, ": invalid index '{}'", s),
+            ObjErrorKind::ZeroNormal => write!(f, ": normal has zero length"),
+            ObjErrorKind::FaceTooSmall => write!(f, ": face has less than 3 vertices"),
+            ObjErrorKind::UnknownMaterial(name) => write!(f, ": unknown material '{}'", name),
+            ObjErrorKind::Image(e) => write!(f, ": {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+/// Loads Wavefront `.obj` file with materials from the referenced `.mtl` libraries.
+/// Each group and material pair becomes a separate mesh, polygons are triangulated.
+pub fn load_obj(path: &Path) -> Result<Vec<TriangleMesh>, ObjError> {
+    let file = fs::File::open(path).map_err(|e| ObjError {
+        path: path.to_path_buf(),
+        line: None,
+        kind: ObjErrorKind::IO(e),
+    })?;
+    parse(path, io::BufReader::new(file))
+}
+
+/// Unique combination of position, uv and normal indices.
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+struct Group {
+    material: MaterialArc,
+    remap: HashMap<VertexKey, usize>,
+    vertices: Vec<Vector>,
+    uvs: Vec<Option<(f64, f64)>>,
+    normals: Vec<Option<NormVector>>,
+    indices: Vec<[usize; 3]>,
+}
+
+impl Group {
+    fn new(material: MaterialArc) -> Self {
+        Group {
+            material,
+            remap: HashMap::new(),
+            vertices: vec![],
+            uvs: vec![],
+            normals: vec![],
+            indices: vec![],
+        }
+    }
+
+    fn vertex(&mut self, key: VertexKey, data: &Data) -> usize {
+        if let Some(i) = self.remap.get(&key) {
+            return *i;
+        }
+        let (v, vt, vn) = key;
+        let i = self.vertices.len();
+        self.vertices.push(data.vertices[v]);
+        self.uvs.push(vt.map(|vt| data.uvs[vt]));
+        self.normals.push(vn.map(|vn| data.normals[vn].clone()));
+        self.remap.insert(key, i);
+        i
+    }
+
+    fn build(self) -> TriangleMesh {
+        let mut mesh = TriangleMesh::new()
+            .vertices(self.vertices)
+            .indices(self.indices)
+            .material(self.material);
+        if let Some(uvs) = self.uvs.into_iter().collect() {
+            mesh = mesh.uvs(uvs);
+        }
+        if let Some(normals) = self.normals.into_iter().collect() {
+            mesh = mesh.normals(normals);
+        }
+        mesh.build()
+    }
+}
+
+#[derive(Default)]
+struct Data {
+    vertices: Vec<Vector>,
+    uvs: Vec<(f64, f64)>,
+    normals: Vec<NormVector>,
+}
+
+fn parse(path: &Path, reader: impl BufRead) -> Result<Vec<TriangleMesh>, ObjError> {
+    let default_material: MaterialArc = Arc::new(Lambertian::new(
+        image::Color { r: 200, g: 200, b: 200 },
+    ));
+    let mut materials = HashMap::new();
+    let mut data = Data::default();
+    let mut groups: Vec<Group> = vec![];
+    let mut group_index = HashMap::new();
+    let mut group_name = String::new();
+    let mut material_name: Option<String> = None;
+    let mut current = None;
+
+    for (i, line) in reader.lines().enumerate() {
+        let err = |kind| ObjError { path: path.to_path_buf(), line: Some(i + 1), kind };
+        let line = line.map_err(|e| err(ObjErrorKind::IO(e)))?;
+        let mut args = line.split_whitespace();
+        match args.next() {
+            Some("v") => {
+                let [x, y, z] = floats(&mut args, "vertex coordinate").map_err(err)?;
+                data.vertices.push(Vector::new(x, y, z));
+            }
+            Some("vt") => {
+                let u = float(args.next(), "texture coordinate").map_err(err)?;
+                let v = match args.next() {
+                    Some(v) => float(Some(v), "texture coordinate").map_err(err)?,
+                    None => 0.,
+                };
+                data.uvs.push((u, v));
+            }
+            Some("vn") => {
+                let [x, y, z] = floats(&mut args, "normal coordinate").map_err(err)?;
+                let n = Vector::new(x, y, z);
+                if !(n.norm() > 0. && n.norm().is_finite()) {
+                    return Err(err(ObjErrorKind::ZeroNormal));
+                }
+                data.normals.push(NormVector::from(n));
+            }
+            Some("f") => {
+                let keys = args
+                    .map(|arg| vertex_key(arg, &data))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(err)?;
+                if keys.len() < 3 {
+                    return Err(err(ObjErrorKind::FaceTooSmall));
+                }
+                let g = *current.get_or_insert_with(|| {
+                    let key = (group_name.clone(), material_name.clone());
+                    *group_index.entry(key).or_insert_with(|| {
+                        let material = material_name
+                            .as_ref()
+                            .map_or(&default_material, |name| &materials[name]);
+                        groups.push(Group::new(Arc::clone(material)));
+                        groups.len() - 1
+                    })
+                });
+                let group = &mut groups[g];
+                let first = group.vertex(keys[0], &data);
+                for pair in keys[1..].windows(2) {
+                    let b = group.vertex(pair[0], &data);
+                    let c = group.vertex(pair[1], &data);
+                    group.indices.push([first, b, c]);
+                }
+            }
+            Some("g") | Some("o") => {
+                group_name = args.collect::<Vec<_>>().join(" ");
+                current = None;
+            }
+            Some("usemtl") => {
+                let name = args.next().ok_or_else(|| err(ObjErrorKind::MissingValue("material name")))?;
+                if !materials.contains_key(name) {
+                    return Err(err(ObjErrorKind::UnknownMaterial(name.to_string())));
+                }
+                material_name = Some(name.to_string());
+                current = None;
+            }
+            Some("mtllib") => {
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                for lib in args {
+                    materials.extend(mtl::load_mtl(&dir.join(lib))?);
+                }
+            }
+            // Comments, smoothing groups, lines and other unsupported statements.
+            _ => {}
+        }
+    }
+    Ok(groups.into_iter().map(Group::build).collect())
+}
+
+fn vertex_key(arg: &str, data: &Data) -> Result<VertexKey, ObjErrorKind> {
+    let mut parts = arg.split('/');
+    let v = index(parts.next(), data.vertices.len())?.ok_or_else(|| {
+        ObjErrorKind::InvalidIndex(arg.to_string())
+    })?;
+    let vt = index(parts.next(), data.uvs.len())?;
+    let vn = index(parts.next(), data.normals.len())?;
+    Ok((v, vt, vn))
+}
+
+/// Resolves 1-based or negative relative index into 0-based, empty index is absent.
+fn index(s: Option<&str>, len: usize) -> Result<Option<usize>, ObjErrorKind> {
+    let s = match s {
+        None | Some("") => return Ok(None),
+        Some(s) => s,
+    };
+    let invalid = || ObjErrorKind::InvalidIndex(s.to_string());
+    let i: i64 = s.parse().map_err(|_| invalid())?;
+    let i = if i < 0 { len as i64 + i } else { i - 1 };
+    if 0 <= i && (i as usize) < len {
+        Ok(Some(i as usize))
+    } else {
+        Err(invalid())
+    }
+}
+
+pub(crate) fn float(s: Option<&str>, what: &'static str) -> Result<f64, ObjErrorKind> {
+    let s = s.ok_or(ObjErrorKind::MissingValue(what))?;
+    s.parse().map_err(|_| ObjErrorKind::InvalidNumber(s.to_string()))
+}
+
+pub(crate) fn floats(args: &mut SplitWhitespace, what: &'static str) -> Result<[f64; 3], ObjErrorKind> {
+    Ok([
+        float(args.next(), what)?,
+        float(args.next(), what)?,
+        float(args.next(), what)?,
+    ])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::objs::Touch;
+    use crate::ray::Ray;
+
+    fn load(obj: &str) -> Result<Vec<TriangleMesh>, ObjError> {
+        parse(Path::new("/nonexistent/test.obj"), obj.as_bytes())
+    }
+
+    fn error(obj: &str) -> ObjError {
+        load(obj).err().unwrap()
+    }
+
+    /// Whether the ray going down through `(x, y)` touches the mesh.
+    fn covers(mesh: &TriangleMesh, x: f64, y: f64) -> bool {
+        let r = Ray { orig: Vector::new(x, y, 1.), dir: NormVector::new(0., 0., -1.) };
+        mesh.touch(&r, 0.001, f64::MAX).is_some()
+    }
+
+    #[test]
+    fn polygons() {
+        let meshes = load("v 0 0 0\nv 2 0 0\nv 2 1 0\nv 1 2 0\nv 0 1 0\nf 1 2 3 4 5\n").unwrap();
+        assert_eq!(meshes.len(), 1);
+        for (x, y) in [(0.1, 0.1), (1.9, 0.1), (1.9, 0.9), (1., 1.9), (0.1, 0.9), (1., 1.)] {
+            assert!(covers(&meshes[0], x, y));
+        }
+        assert!(!covers(&meshes[0], 1.9, 1.5));
+    }
+
+    #[test]
+    fn relative_indices() {
+        let obj = "v 5 5 0\nv 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 1\nf -3//-1 -2//-1 -1//-1\n";
+        let meshes = load(obj).unwrap();
+        assert!(covers(&meshes[0], 0.25, 0.25));
+        assert!(!covers(&meshes[0], 2., 2.));
+    }
+
+    #[test]
+    fn bad_index() {
+        let e = error("v 0 0 0\nv 1 0 0\nv 0 1 0\n\nf 1 2 4\n");
+        assert_eq!(e.line, Some(5));
+        assert!(matches!(e.kind, ObjErrorKind::InvalidIndex(ref s) if s == "4"));
+        assert!(matches!(error("v 0 0 0\nf 1 -2 1\n").kind, ObjErrorKind::InvalidIndex(_)));
+        assert!(matches!(error("v 0 0 0\nf 1 1/x 1\n").kind, ObjErrorKind::InvalidIndex(_)));
+        assert!(matches!(error("v 0 0 0\nf 1 1\n").kind, ObjErrorKind::FaceTooSmall));
+    }
+
+    #[test]
+    fn malformed_float() {
+        let e = error("# comment\nv 0 0.5.1 0\n");
+        assert_eq!(e.line, Some(2));
+        assert!(matches!(e.kind, ObjErrorKind::InvalidNumber(ref s) if s == "0.5.1"));
+        assert!(matches!(error("vn 1 0\n").kind, ObjErrorKind::MissingValue(_)));
+        assert_eq!(error("v 0 0 0\nv 0 x 0\n").to_string(), "/nonexistent/test.obj:2: invalid number 'x'");
+    }
+
+    #[test]
+    fn zero_normal() {
+        let e = error("vn 0 0 1\nvn 0 0 0\n");
+        assert_eq!(e.line, Some(2));
+        assert!(matches!(e.kind, ObjErrorKind::ZeroNormal));
+    }
+
+    #[test]
+    fn missing_mtl() {
+        let e = error("mtllib missing.mtl\n");
+        assert_eq!(e.path, Path::new("/nonexistent/missing.mtl"));
+        assert_eq!(e.line, None);
+        assert!(matches!(e.kind, ObjErrorKind::IO(ref e) if e.kind() == io::ErrorKind::NotFound));
+        assert!(matches!(error("usemtl red\n").kind, ObjErrorKind::UnknownMaterial(_)));
+    }
+}
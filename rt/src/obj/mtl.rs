@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::io::BufRead;
+use std::path::Path;
+use std::sync::Arc;
+
+use color::Color;
+
+use crate::objs::{Dielectric, DiffuseLight, Lambertian, MaterialArc, Metal};
+use crate::obj::{float, floats, ObjError, ObjErrorKind};
+use crate::textures::{ImageTexture, TextureArc};
+use crate::utils::{Positive, UniFloat};
+
+/// Material statements of the `.mtl` file, absent ones take the format defaults.
+struct Mtl {
+    diffuse: [f64; 3],
+    /// Replaces the diffuse color.
+    diffuse_map: Option<TextureArc>,
+    specular: [f64; 3],
+    emissive: [f64; 3],
+    shininess: f64,
+    refraction_index: f64,
+    dissolve: f64,
+    illum: u32,
+}
+
+impl Default for Mtl {
+    fn default() -> Self {
+        Mtl {
+            diffuse: [0.8; 3],
+            diffuse_map: None,
+            specular: [0.; 3],
+            emissive: [0.; 3],
+            shininess: 0.,
+            refraction_index: 1.,
+            dissolve: 1.,
+            illum: 2,
+        }
+    }
+}
+
+impl Mtl {
+    /// Picks the closest of the supported materials.
+    fn material(&self) -> MaterialArc {
+        if self.emissive.iter().any(|x| *x > 0.) {
+            Arc::new(DiffuseLight { emit: hdr_color(self.emissive) })
+        } else if self.dissolve < 1. || [4, 6, 7, 9].contains(&self.illum) {
+            Arc::new(Dielectric {
+                refraction_index: Positive::new(self.refraction_index)
+                    .unwrap_or_else(|| Positive::new(1.5).unwrap()),
+            })
+        } else if self.illum == 3 || self.illum == 5 {
+            let fuzz = (1. - self.shininess / 1000.).clamp(0., 1.);
+            Arc::new(Metal::new(hdr_color(self.specular), UniFloat::new(fuzz).unwrap()))
+        } else if let Some(map) = &self.diffuse_map {
+            Arc::new(Lambertian { albedo: Arc::clone(map) })
+        } else {
+            Arc::new(Lambertian::new(hdr_color(self.diffuse)))
+        }
+    }
+}
+
+fn hdr_color([r, g, b]: [f64; 3]) -> Color<f64> {
+    Color { r, g, b }
+}
+
+pub(crate) fn load_mtl(path: &Path) -> Result<HashMap<String, MaterialArc>, ObjError> {
+    let file = fs::File::open(path).map_err(|e| ObjError {
+        path: path.to_path_buf(),
+        line: None,
+        kind: ObjErrorKind::IO(e),
+    })?;
+
+    let mut materials = HashMap::new();
+    let mut current: Option<(String, Mtl)> = None;
+    for (i, line) in io::BufReader::new(file).lines().enumerate() {
+        let err = |kind| ObjError { path: path.to_path_buf(), line: Some(i + 1), kind };
+        let line = line.map_err(|e| err(ObjErrorKind::IO(e)))?;
+        let mut args = line.split_whitespace();
+        let statement = args.next();
+        if statement == Some("newmtl") {
+            let name = args.next().ok_or_else(|| err(ObjErrorKind::MissingValue("material name")))?;
+            if let Some((name, mtl)) = current.replace((name.to_string(), Mtl::default())) {
+                materials.insert(name, mtl.material());
+            }
+            continue;
+        }
+        let mtl = match &mut current {
+            Some((_, mtl)) => mtl,
+            None => continue,
+        };
+        match statement {
+            Some("Kd") => mtl.diffuse = floats(&mut args, "color component").map_err(err)?,
+            Some("map_Kd") => {
+                // Options like `-s 1 1 1` precede the file name.
+                let name = args.last().ok_or_else(|| err(ObjErrorKind::MissingValue("texture file")))?;
+                let dir = path.parent().unwrap_or_else(|| Path::new(""));
+                let texture = ImageTexture::load(&dir.join(name)).map_err(|e| err(ObjErrorKind::Image(e)))?;
+                mtl.diffuse_map = Some(Arc::new(texture));
+            }
+            Some("Ks") => mtl.specular = floats(&mut args, "color component").map_err(err)?,
+            Some("Ke") => mtl.emissive = floats(&mut args, "color component").map_err(err)?,
+            Some("Ns") => mtl.shininess = float(args.next(), "shininess").map_err(err)?,
+            Some("Ni") => mtl.refraction_index = float(args.next(), "refraction index").map_err(err)?,
+            Some("d") => mtl.dissolve = float(args.next(), "dissolve").map_err(err)?,
+            Some("Tr") => mtl.dissolve = 1. - float(args.next(), "transparency").map_err(err)?,
+            Some("illum") => {
+                let s = args.next().ok_or_else(|| err(ObjErrorKind::MissingValue("illumination model")))?;
+                mtl.illum = s.parse().map_err(|_| err(ObjErrorKind::InvalidNumber(s.to_string())))?;
+            }
+            // Comments, other texture maps and unsupported statements.
+            _ => {}
+        }
+    }
+    if let Some((name, mtl)) = current {
+        materials.insert(name, mtl.material());
+    }
+    Ok(materials)
+}
@@ -0,0 +1,79 @@
+use color::Color;
+
+use crate::objs::{Material, Scatter, Touching};
+use crate::ray::Ray;
+use crate::sampler::Samples;
+use crate::utils::{clone_vec, Positive, reflect, refract};
+
+pub struct Dielectric {
+    pub refraction_index: Positive<f64>,
+}
+
+impl Material for Dielectric {
+    fn scatter(
+        &self,
+        Ray { dir, .. }: &Ray,
+        Touching { normal, p, front_face, .. }: &Touching,
+        samples: &mut Samples,
+    ) -> Option<Scatter> {
+        let ratio = if *front_face {
+            1. / self.refraction_index.get()
+        } else {
+            self.refraction_index.get()
+        };
+        let cos = (-dir.dot(normal)).min(1.);
+        let sin = (1. - cos * cos).sqrt();
+        let total_reflection = ratio * sin > 1.;
+        let dir = if total_reflection || schlick(cos, ratio) > samples.get_1d() {
+            reflect(dir, normal)
+        } else {
+            refract(dir, normal, ratio)
+        };
+        Some(Scatter {
+            attenuation: Color { r: 1., g: 1., b: 1. },
+            scattered: Ray { orig: clone_vec(p), dir },
+            pdf: None,
+        })
+    }
+
+    /// Glass passes all colors through.
+    fn albedo(&self, _: &Touching) -> Color<f64> {
+        Color { r: 1., g: 1., b: 1. }
+    }
+}
+
+/// Schlick's approximation of the Fresnel reflectance.
+fn schlick(cos: f64, ratio: f64) -> f64 {
+    let r0 = ((1. - ratio) / (1. + ratio)).powi(2);
+    r0 + (1. - r0) * (1. - cos).powi(5)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use super::*;
+    use crate::utils::NormVector;
+
+    #[test]
+    fn schlick_normal_and_grazing() {
+        // Glass seen from the air reflects 4% at normal incidence and everything at grazing one.
+        assert_abs_diff_eq!(schlick(1., 1. / 1.5), 0.04, epsilon = 1e-12);
+        assert_abs_diff_eq!(schlick(0., 1. / 1.5), 1., epsilon = 1e-12);
+        assert!(schlick(0.5, 1. / 1.5) > schlick(0.9, 1. / 1.5));
+    }
+
+    #[test]
+    fn snell() {
+        let n = NormVector::new(0., 1., 0.);
+        let sin = 0.5_f64;
+        let v = NormVector::new(sin, -(1. - sin * sin).sqrt(), 0.);
+        let refracted = refract(&v, &n, 1. / 1.5);
+        assert_abs_diff_eq!(refracted.x, sin / 1.5, epsilon = 1e-12);
+        assert!(refracted.y < 0.);
+        // At the critical angle the refracted ray runs along the surface.
+        let v = NormVector::new(1. / 1.5, -(1. - 1. / 2.25_f64).sqrt(), 0.);
+        let refracted = refract(&v, &n, 1.5);
+        assert_abs_diff_eq!(refracted.y, 0., epsilon = 1e-6);
+    }
+}
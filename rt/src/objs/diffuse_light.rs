@@ -0,0 +1,35 @@
+use color::Color;
+
+use crate::objs::{Material, Scatter, Touching};
+use crate::ray::Ray;
+use crate::sampler::Samples;
+
+/// Emits light evenly in all directions and absorbs everything coming in.
+/// Radiance `1.0` matches the white background, greater values are allowed.
+pub struct DiffuseLight {
+    pub emit: Color<f64>,
+}
+
+impl Material for DiffuseLight {
+    fn scatter(&self, _: &Ray, _: &Touching, _: &mut Samples) -> Option<Scatter> {
+        None
+    }
+
+    fn emitted(&self, _: &Touching) -> Color<f64> {
+        self.emit
+    }
+
+    /// Hue of the light at full brightness.
+    fn albedo(&self, _: &Touching) -> Color<f64> {
+        let max = self.emit.r.max(self.emit.g).max(self.emit.b);
+        if max > 0. {
+            (1. / max) * self.emit
+        } else {
+            self.emit
+        }
+    }
+
+    fn is_emitter(&self) -> bool {
+        self.emit.r > 0. || self.emit.g > 0. || self.emit.b > 0.
+    }
+}
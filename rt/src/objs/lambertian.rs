@@ -1,21 +1,47 @@
-use image::Color;
+use std::f64::consts::PI;
+use std::sync::Arc;
+
+use color::Color;
 
 use crate::objs::{Material, Scatter, Touching};
 use crate::ray::Ray;
+use crate::textures::{Texture, TextureArc};
+use crate::sampler::Samples;
 use crate::utils::{clone_vec, NormVector, random_unit};
 
 pub struct Lambertian {
-    pub albedo: Color
+    /// Used to be a plain color, `Lambertian::new` still takes one as a texture.
+    pub albedo: TextureArc,
+}
+
+impl Lambertian {
+    pub fn new(albedo: impl Texture + Send + Sync + 'static) -> Self {
+        Lambertian { albedo: Arc::new(albedo) }
+    }
 }
 
 impl Material for Lambertian {
-    fn scatter(&self, _: &Ray, Touching { p, normal, .. }: &Touching) -> Option<Scatter> {
+    /// Cosine-weighted scattering, the density cancels out the cosine and `1 / PI`.
+    fn scatter(&self, _: &Ray, touching: &Touching, samples: &mut Samples) -> Option<Scatter> {
+        let Touching { p, normal, uv, .. } = touching;
+        let dir = NormVector::from(normal.get() + random_unit(samples));
         Some(Scatter {
-            attenuation: self.albedo,
-            scattered: Ray {
-                orig: clone_vec(p),
-                dir: NormVector::from(normal.get() + random_unit()),
-            },
+            attenuation: self.albedo.value(*uv, p),
+            pdf: Some(self.pdf(touching, &dir)),
+            scattered: Ray { orig: clone_vec(p), dir },
         })
     }
+
+    fn albedo(&self, Touching { p, uv, .. }: &Touching) -> Color<f64> {
+        self.albedo.value(*uv, p)
+    }
+
+    fn bsdf(&self, touching: &Touching, dir: &NormVector) -> Color<f64> {
+        let Touching { p, uv, .. } = touching;
+        self.pdf(touching, dir) * self.albedo.value(*uv, p)
+    }
+
+    fn pdf(&self, Touching { normal, .. }: &Touching, dir: &NormVector) -> f64 {
+        dir.dot(normal).max(0.) / PI
+    }
 }
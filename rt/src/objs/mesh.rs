@@ -0,0 +1,175 @@
+use std::sync::Arc;
+
+use crate::bvh::{Accel, Bvh};
+use crate::objs::{Emitter, MaterialArc, Touch, TouchBox, Touching, triangle};
+use crate::ray::Ray;
+use crate::sampler::Samples;
+use crate::utils::{Aabb, NormVector};
+use crate::Vector;
+
+/// Triangles sharing vertices, normals and uvs by indices.
+pub struct TriangleMesh {
+    pub(crate) bounds: Aabb,
+    pub(crate) triangles: Bvh,
+    data: Arc<MeshData>,
+    indices: Vec<[usize; 3]>,
+    /// Running sums of the triangle areas.
+    areas: Vec<f64>,
+}
+
+impl TriangleMesh {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> TriangleMeshBuilder {
+        TriangleMeshBuilder::new()
+    }
+}
+
+struct MeshData {
+    vertices: Vec<Vector>,
+    normals: Option<Vec<NormVector>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    material: MaterialArc,
+}
+
+struct MeshTriangle {
+    mesh: Arc<MeshData>,
+    indices: [usize; 3],
+}
+
+pub struct TriangleMeshBuilder {
+    vertices: Vec<Vector>,
+    indices: Vec<[usize; 3]>,
+    normals: Option<Vec<NormVector>>,
+    uvs: Option<Vec<(f64, f64)>>,
+    material: Option<MaterialArc>,
+}
+
+impl Default for TriangleMeshBuilder {
+    fn default() -> Self {
+        TriangleMeshBuilder::new()
+    }
+}
+
+impl TriangleMeshBuilder {
+    pub fn new() -> Self {
+        TriangleMeshBuilder {
+            vertices: vec![],
+            indices: vec![],
+            normals: None,
+            uvs: None,
+            material: None,
+        }
+    }
+
+    pub fn vertices(mut self, vertices: Vec<Vector>) -> Self {
+        self.vertices = vertices;
+        self
+    }
+
+    /// Triangles as triples of vertex indices in counter-clockwise order.
+    pub fn indices(mut self, indices: Vec<[usize; 3]>) -> Self {
+        self.indices = indices;
+        self
+    }
+
+    /// Per-vertex normals interpolated for smooth shading.
+    pub fn normals(mut self, normals: Vec<NormVector>) -> Self {
+        self.normals = Some(normals);
+        self
+    }
+
+    pub fn uvs(mut self, uvs: Vec<(f64, f64)>) -> Self {
+        self.uvs = Some(uvs);
+        self
+    }
+
+    material_setters!();
+
+    pub fn build(self) -> TriangleMesh {
+        let n = self.vertices.len();
+        assert!(self.indices.iter().flatten().all(|i| *i < n), "Vertex index out of bounds");
+        assert!(self.normals.as_ref().is_none_or(|ns| ns.len() == n), "Normal per vertex expected");
+        assert!(self.uvs.as_ref().is_none_or(|uvs| uvs.len() == n), "Uv per vertex expected");
+        let mesh = Arc::new(MeshData {
+            vertices: self.vertices,
+            normals: self.normals,
+            uvs: self.uvs,
+            material: self.material.unwrap(),
+        });
+        let triangles: Vec<TouchBox> = self.indices
+            .iter()
+            .map(|indices| -> TouchBox {
+                Box::new(MeshTriangle { mesh: Arc::clone(&mesh), indices: *indices })
+            })
+            .collect();
+        let triangles = Bvh::new(triangles, Accel::Bvh);
+        let areas = self.indices
+            .iter()
+            .scan(0., |sum, [a, b, c]| {
+                let vertices = &mesh.vertices;
+                *sum += triangle::area([&vertices[*a], &vertices[*b], &vertices[*c]]);
+                Some(*sum)
+            })
+            .collect();
+        TriangleMesh {
+            bounds: triangles.bounds(),
+            triangles,
+            data: mesh,
+            indices: self.indices,
+            areas,
+        }
+    }
+}
+
+impl Touch for TriangleMesh {
+    fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching> {
+        self.triangles.touch(r, t_min, t_max)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.bounds.clone()
+    }
+}
+
+impl TriangleMesh {
+    /// Whether the mesh has emissive material and non-zero area.
+    pub(crate) fn is_emitter(&self) -> bool {
+        self.data.material.is_emitter() && self.areas.last().is_some_and(|area| *area > 0.)
+    }
+}
+
+/// Triangles are chosen proportionally to their areas.
+impl Emitter for TriangleMesh {
+    fn sample(&self, from: &Vector, samples: &mut Samples) -> NormVector {
+        let total = self.areas.last().copied().unwrap_or(0.);
+        let x = samples.get_1d() * total;
+        let i = self.areas.partition_point(|sum| *sum <= x).min(self.indices.len() - 1);
+        let [a, b, c] = self.indices[i];
+        let vertices = &self.data.vertices;
+        NormVector::from(triangle::sample([&vertices[a], &vertices[b], &vertices[c]], samples) - from)
+    }
+
+    fn pdf(&self, from: &Vector, touching: &Touching) -> f64 {
+        triangle::area_pdf(from, touching, self.areas.last().copied().unwrap_or(0.))
+    }
+}
+
+impl Touch for MeshTriangle {
+    fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching> {
+        let MeshData { vertices, normals, uvs, material } = &*self.mesh;
+        let [a, b, c] = self.indices;
+        triangle::touch(
+            r, t_min, t_max,
+            [&vertices[a], &vertices[b], &vertices[c]],
+            normals.as_ref().map(|ns| [&ns[a], &ns[b], &ns[c]]),
+            uvs.as_ref().map(|uvs| [uvs[a], uvs[b], uvs[c]]),
+            material,
+        )
+    }
+
+    fn bounds(&self) -> Aabb {
+        let [a, b, c] = self.indices;
+        let vertices = &self.mesh.vertices;
+        triangle::bounds([&vertices[a], &vertices[b], &vertices[c]].iter().copied())
+    }
+}
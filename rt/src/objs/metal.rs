@@ -1,31 +1,48 @@
-use image::Color;
+use std::sync::Arc;
+
+use color::Color;
 
 use crate::objs::{Material, Scatter, Touching};
 use crate::ray::Ray;
+use crate::textures::{Texture, TextureArc};
+use crate::sampler::Samples;
 use crate::utils::{clone_vec, NormVector, random_unit, reflect, UniFloat};
 
 pub struct Metal {
-    pub albedo: Color,
+    /// Used to be a plain color, `Metal::new` still takes one as a texture.
+    pub albedo: TextureArc,
     pub fuzz: UniFloat<f64>,
 }
 
+impl Metal {
+    pub fn new(albedo: impl Texture + Send + Sync + 'static, fuzz: UniFloat<f64>) -> Self {
+        Metal { albedo: Arc::new(albedo), fuzz }
+    }
+}
+
 impl Material for Metal {
     fn scatter(
         &self,
         Ray { dir, .. }: &Ray,
-        Touching { normal, p, .. }: &Touching,
+        Touching { normal, p, uv, .. }: &Touching,
+        samples: &mut Samples,
     ) -> Option<Scatter> {
         let reflected = reflect(dir, normal);
         if reflected.dot(normal) > 0. {
             Some(Scatter {
-                attenuation: self.albedo,
+                attenuation: self.albedo.value(*uv, p),
                 scattered: Ray {
                     orig: clone_vec(p),
-                    dir: NormVector::from(reflected.get() + self.fuzz.get() * random_unit()),
+                    dir: NormVector::from(reflected.get() + self.fuzz.get() * random_unit(samples)),
                 },
+                pdf: None,
             })
         } else {
             None
         }
     }
+
+    fn albedo(&self, Touching { p, uv, .. }: &Touching) -> Color<f64> {
+        self.albedo.value(*uv, p)
+    }
 }
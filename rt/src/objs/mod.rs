@@ -1,40 +1,149 @@
 use std::sync::Arc;
 
 pub use {
+    dielectric::Dielectric,
+    diffuse_light::DiffuseLight,
     lambertian::Lambertian,
+    mesh::{TriangleMesh, TriangleMeshBuilder},
     metal::Metal,
     sphere::{Sphere, SphereBuilder},
+    triangle::{Triangle, TriangleBuilder},
 };
-use image::Color;
+use color::Color;
 
 use crate::ray::Ray;
-use crate::utils::{NormVector, Positive};
+use crate::sampler::Samples;
+use crate::utils::{Aabb, NormVector, Positive};
 use crate::Vector;
 
+/// Builder methods setting `self.material` to one of the materials.
+macro_rules! material_setters {
+    () => {
+        pub fn metal(mut self, metal: crate::Metal) -> Self {
+            self.material = Some(std::sync::Arc::new(metal));
+            self
+        }
+
+        pub fn lambertian(mut self, lambertian: crate::Lambertian) -> Self {
+            self.material = Some(std::sync::Arc::new(lambertian));
+            self
+        }
+
+        pub fn dielectric(mut self, dielectric: crate::Dielectric) -> Self {
+            self.material = Some(std::sync::Arc::new(dielectric));
+            self
+        }
+
+        pub fn diffuse_light(mut self, light: crate::DiffuseLight) -> Self {
+            self.material = Some(std::sync::Arc::new(light));
+            self
+        }
+
+        pub(crate) fn material(mut self, material: crate::objs::MaterialArc) -> Self {
+            self.material = Some(material);
+            self
+        }
+    };
+}
+
 mod sphere;
+mod triangle;
+mod mesh;
 mod lambertian;
 mod metal;
+mod dielectric;
+mod diffuse_light;
 
 pub(crate) trait Touch {
-    fn touch(&self, r: &Ray) -> Option<Touching>;
+    /// Nearest touching with `t` inside of `(t_min, t_max)`.
+    fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching>;
+
+    fn bounds(&self) -> Aabb;
 }
 
 pub(crate) type TouchBox = Box<dyn Touch + Send + Sync + 'static>;
 
+/// Emissive surface sampled directly by the integrator.
+pub(crate) trait Emitter: Touch {
+    /// Random direction from `from` towards the surface.
+    fn sample(&self, from: &Vector, samples: &mut Samples) -> NormVector;
+
+    /// Solid angle density of `sample` producing the direction from `from` to the touching.
+    fn pdf(&self, from: &Vector, touching: &Touching) -> f64;
+}
+
+pub(crate) type EmitterArc = Arc<dyn Emitter + Send + Sync + 'static>;
+
+/// Emitter registered as the scene light with the index, its touchings refer to it.
+pub(crate) struct Light {
+    pub(crate) index: usize,
+    pub(crate) emitter: EmitterArc,
+}
+
+impl Touch for Light {
+    fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching> {
+        let mut touching = self.emitter.touch(r, t_min, t_max)?;
+        touching.light = Some(self.index);
+        Some(touching)
+    }
+
+    fn bounds(&self) -> Aabb {
+        self.emitter.bounds()
+    }
+}
+
 pub(crate) struct Touching {
     pub(crate) p: Vector,
     pub(crate) t: Positive<f64>,
+    /// Always directed against the ray.
     pub(crate) normal: NormVector,
+    /// Normal of the surface itself on the side of `normal`,
+    /// which differs from it when vertex normals smooth the shading.
+    pub(crate) geometric_normal: NormVector,
+    /// Whether the ray came from the outer side of the surface.
+    pub(crate) front_face: bool,
+    /// Surface coordinates of the touching point.
+    pub(crate) uv: (f64, f64),
     pub(crate) material: MaterialArc,
+    /// Index of the scene light the touched surface belongs to.
+    pub(crate) light: Option<usize>,
 }
 
 pub(crate) struct Scatter {
-    pub(crate) attenuation: Color,
+    /// Fraction of the scattered radiance reaching the viewer per channel.
+    pub(crate) attenuation: Color<f64>,
     pub(crate) scattered: Ray,
+    /// Solid angle density of the scattered direction, `None` for specular scattering.
+    pub(crate) pdf: Option<f64>,
 }
 
 pub(crate) trait Material {
-    fn scatter(&self, r: &Ray, t: &Touching) -> Option<Scatter>;
+    fn scatter(&self, r: &Ray, t: &Touching, samples: &mut Samples) -> Option<Scatter>;
+
+    fn emitted(&self, _: &Touching) -> Color<f64> {
+        Color { r: 0., g: 0., b: 0. }
+    }
+
+    /// Color of the surface itself in `[0, 1]`, written to the albedo AOV for denoisers.
+    fn albedo(&self, _: &Touching) -> Color<f64> {
+        Color { r: 0., g: 0., b: 0. }
+    }
+
+    /// Whether surfaces of the material are sampled as lights.
+    fn is_emitter(&self) -> bool {
+        false
+    }
+
+    /// Fraction of radiance coming along `dir` scattered to the viewer,
+    /// cosine included. Only used for non-specular scattering.
+    fn bsdf(&self, _: &Touching, _: &NormVector) -> Color<f64> {
+        Color { r: 0., g: 0., b: 0. }
+    }
+
+    /// Density of `scatter` producing `dir`.
+    fn pdf(&self, _: &Touching, _: &NormVector) -> f64 {
+        0.
+    }
 }
 
 pub(crate) type MaterialArc = Arc<dyn Material + Send + Sync + 'static>;
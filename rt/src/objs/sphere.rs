@@ -1,12 +1,11 @@
-use std::cmp;
+use std::f64::consts::PI;
 use std::sync::Arc;
 
-use float_ord::FloatOrd;
-
-use crate::{Lambertian, Metal, Vector};
-use crate::objs::{MaterialArc, Touch, Touching};
+use crate::Vector;
+use crate::objs::{Emitter, MaterialArc, Touch, Touching};
 use crate::ray::Ray;
-use crate::utils::{NormVector, Positive};
+use crate::sampler::Samples;
+use crate::utils::{Aabb, NormVector, orthonormal_basis, Positive, random_unit};
 
 pub struct Sphere {
     pub(crate) center: Vector,
@@ -15,6 +14,7 @@ pub struct Sphere {
 }
 
 impl Sphere {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> SphereBuilder {
         SphereBuilder::new()
     }
@@ -26,6 +26,12 @@ pub struct SphereBuilder {
     material: Option<MaterialArc>,
 }
 
+impl Default for SphereBuilder {
+    fn default() -> Self {
+        SphereBuilder::new()
+    }
+}
+
 impl SphereBuilder {
     pub fn new() -> Self {
         SphereBuilder {
@@ -45,15 +51,7 @@ impl SphereBuilder {
         self
     }
 
-    pub fn metal(mut self, metal: Metal) -> Self {
-        self.material = Some(Arc::new(metal));
-        self
-    }
-
-    pub fn lambertian(mut self, lambertian: Lambertian) -> Self {
-        self.material = Some(Arc::new(lambertian));
-        self
-    }
+    material_setters!();
 
     pub fn build(self) -> Sphere {
         Sphere {
@@ -65,31 +63,155 @@ impl SphereBuilder {
 }
 
 impl Touch for Sphere {
-    fn touch(&self, r: &Ray) -> Option<Touching> {
+    fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching> {
         let Ray { orig, dir } = r;
-        let oc = orig - &self.center;
+        let oc = orig - self.center;
 
         let a = dir.dot(dir);
         let b = oc.dot(dir);
         let c = oc.dot(&oc) - self.radius.get() * self.radius.get();
         let d = b * b - a * c;
-        if d <= 0. {
+        if d < 0. {
             return None;
         }
 
         let root = d.sqrt();
-        let t1 = (-b - root) / a;
-        let t2 = (-b + root) / a;
-        let t = cmp::max(FloatOrd(t1), FloatOrd(t2)).0;
-        if t > 0. {
-            Some(Touching {
-                normal: NormVector::from(orig - &self.center),
-                p: r.point(t),
-                t: Positive::new(t).unwrap(),
-                material: Arc::clone(&self.material),
-            })
-        } else {
-            None
+        let t = [(-b - root) / a, (-b + root) / a]
+            .iter()
+            .copied()
+            .find(|t| t_min < *t && *t < t_max)?;
+        let p = r.point(t);
+        let outward = NormVector::from(p - self.center);
+        let front_face = dir.dot(&outward) <= 0.;
+        let uv = uv(&outward);
+        let normal = if front_face { outward } else { NormVector::from_unchecked(-outward.get()) };
+        Some(Touching {
+            geometric_normal: normal.clone(),
+            normal,
+            front_face,
+            uv,
+            p,
+            t: Positive::new(t)?,
+            material: Arc::clone(&self.material),
+            light: None,
+        })
+    }
+
+    fn bounds(&self) -> Aabb {
+        let r = Vector::repeat(self.radius.get());
+        Aabb::new(self.center - r, self.center + r)
+    }
+}
+
+/// Directions are sampled uniformly inside of the cone containing the sphere.
+impl Emitter for Sphere {
+    fn sample(&self, from: &Vector, samples: &mut Samples) -> NormVector {
+        let Some(one_minus_cos_max) = self.one_minus_cos_max(from) else {
+            return NormVector::from(random_unit(samples));
+        };
+        let axis = NormVector::from(self.center - from);
+        let (s, t) = orthonormal_basis(&axis);
+        let (u, v) = samples.get_2d();
+        let cos = 1. - u * one_minus_cos_max;
+        let sin = (1. - cos * cos).max(0.).sqrt();
+        let phi = 2. * PI * v;
+        NormVector::from(cos * axis.get() + sin * phi.cos() * s + sin * phi.sin() * t)
+    }
+
+    fn pdf(&self, from: &Vector, _: &Touching) -> f64 {
+        match self.one_minus_cos_max(from) {
+            Some(one_minus_cos_max) => 1. / (2. * PI * one_minus_cos_max),
+            None => 1. / (4. * PI),
         }
     }
 }
+
+impl Sphere {
+    /// Solid angle of the cone containing the sphere divided by `2 * PI`,
+    /// `None` if `from` is inside of the sphere.
+    fn one_minus_cos_max(&self, from: &Vector) -> Option<f64> {
+        let dist2 = (self.center - from).norm_squared();
+        let sin2 = self.radius.get() * self.radius.get() / dist2;
+        if sin2 >= 1. {
+            return None;
+        }
+        // Avoids cancellation for small and distant spheres.
+        Some(sin2 / (1. + (1. - sin2).sqrt()))
+    }
+}
+
+/// Longitude and latitude of the point `p` on the unit sphere mapped to `[0, 1]`,
+/// `v` grows from the bottom pole to the top one.
+fn uv(p: &NormVector) -> (f64, f64) {
+    let phi = (-p.z).atan2(p.x) + PI;
+    let theta = (-p.y).acos();
+    (phi / (2. * PI), theta / PI)
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use image::Color;
+
+    use super::*;
+    use crate::Lambertian;
+
+    fn sphere() -> Sphere {
+        Sphere::new()
+            .center(Vector::new(0., 0., -5.))
+            .radius(Positive::new(1.).unwrap())
+            .lambertian(Lambertian::new(Color::white()))
+            .build()
+    }
+
+    fn ray(orig: Vector, dir: Vector) -> Ray {
+        Ray { orig, dir: NormVector::from(dir) }
+    }
+
+    #[test]
+    fn outside() {
+        let r = ray(Vector::zeros(), Vector::new(0., 0., -1.));
+        let touching = sphere().touch(&r, 0.001, f64::MAX).unwrap();
+        assert_abs_diff_eq!(touching.t.get(), 4.);
+        assert_abs_diff_eq!(touching.p, Vector::new(0., 0., -4.));
+        assert_abs_diff_eq!(*touching.normal.get(), Vector::new(0., 0., 1.));
+        assert!(touching.front_face);
+    }
+
+    #[test]
+    fn outside_nearest_root_out_of_interval() {
+        let r = ray(Vector::zeros(), Vector::new(0., 0., -1.));
+        let touching = sphere().touch(&r, 4.5, f64::MAX).unwrap();
+        assert_abs_diff_eq!(touching.t.get(), 6.);
+        assert!(!touching.front_face);
+        assert!(sphere().touch(&r, 0.001, 3.).is_none());
+    }
+
+    #[test]
+    fn inside() {
+        let r = ray(Vector::new(0., 0., -5.), Vector::new(1., 0., 0.));
+        let touching = sphere().touch(&r, 0.001, f64::MAX).unwrap();
+        assert_abs_diff_eq!(touching.t.get(), 1.);
+        assert_abs_diff_eq!(touching.p, Vector::new(1., 0., -5.));
+        assert_abs_diff_eq!(*touching.normal.get(), Vector::new(-1., 0., 0.));
+        assert!(!touching.front_face);
+    }
+
+    #[test]
+    fn tangent() {
+        let r = ray(Vector::new(1., 0., 0.), Vector::new(0., 0., -1.));
+        let touching = sphere().touch(&r, 0.001, f64::MAX).unwrap();
+        assert_abs_diff_eq!(touching.t.get(), 5.);
+        assert_abs_diff_eq!(touching.p, Vector::new(1., 0., -5.));
+        assert_abs_diff_eq!(*touching.normal.get(), Vector::new(1., 0., 0.));
+    }
+
+    #[test]
+    fn miss() {
+        let r = ray(Vector::new(1.5, 0., 0.), Vector::new(0., 0., -1.));
+        assert!(sphere().touch(&r, 0.001, f64::MAX).is_none());
+        let r = ray(Vector::zeros(), Vector::new(0., 0., 1.));
+        assert!(sphere().touch(&r, 0.001, f64::MAX).is_none());
+    }
+}
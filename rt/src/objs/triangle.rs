@@ -0,0 +1,276 @@
+use std::sync::Arc;
+
+use crate::objs::{Emitter, MaterialArc, Touch, Touching};
+use crate::ray::Ray;
+use crate::sampler::Samples;
+use crate::utils::{Aabb, NormVector, Positive};
+use crate::Vector;
+
+/// Triangle with counter-clockwise front face.
+pub struct Triangle {
+    pub(crate) vertices: [Vector; 3],
+    pub(crate) normals: Option<[NormVector; 3]>,
+    pub(crate) uvs: Option<[(f64, f64); 3]>,
+    pub(crate) material: MaterialArc,
+}
+
+impl Triangle {
+    #[allow(clippy::new_ret_no_self)]
+    pub fn new() -> TriangleBuilder {
+        TriangleBuilder::new()
+    }
+}
+
+pub struct TriangleBuilder {
+    vertices: Option<[Vector; 3]>,
+    normals: Option<[NormVector; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: Option<MaterialArc>,
+}
+
+impl Default for TriangleBuilder {
+    fn default() -> Self {
+        TriangleBuilder::new()
+    }
+}
+
+impl TriangleBuilder {
+    pub fn new() -> Self {
+        TriangleBuilder {
+            vertices: None,
+            normals: None,
+            uvs: None,
+            material: None,
+        }
+    }
+
+    pub fn vertices(mut self, a: Vector, b: Vector, c: Vector) -> Self {
+        self.vertices = Some([a, b, c]);
+        self
+    }
+
+    /// Per-vertex normals interpolated for smooth shading.
+    pub fn normals(mut self, a: NormVector, b: NormVector, c: NormVector) -> Self {
+        self.normals = Some([a, b, c]);
+        self
+    }
+
+    pub fn uvs(mut self, a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> Self {
+        self.uvs = Some([a, b, c]);
+        self
+    }
+
+    material_setters!();
+
+    pub fn build(self) -> Triangle {
+        Triangle {
+            vertices: self.vertices.unwrap(),
+            normals: self.normals,
+            uvs: self.uvs,
+            material: self.material.unwrap(),
+        }
+    }
+}
+
+impl Touch for Triangle {
+    fn touch(&self, r: &Ray, t_min: f64, t_max: f64) -> Option<Touching> {
+        let [a, b, c] = &self.vertices;
+        let normals = self.normals.as_ref().map(|[a, b, c]| [a, b, c]);
+        touch(r, t_min, t_max, [a, b, c], normals, self.uvs, &self.material)
+    }
+
+    fn bounds(&self) -> Aabb {
+        bounds(self.vertices.iter())
+    }
+}
+
+impl Emitter for Triangle {
+    fn sample(&self, from: &Vector, samples: &mut Samples) -> NormVector {
+        let [a, b, c] = &self.vertices;
+        NormVector::from(sample([a, b, c], samples) - from)
+    }
+
+    fn pdf(&self, from: &Vector, touching: &Touching) -> f64 {
+        let [a, b, c] = &self.vertices;
+        area_pdf(from, touching, area([a, b, c]))
+    }
+}
+
+pub(crate) fn area([a, b, c]: [&Vector; 3]) -> f64 {
+    0.5 * (b - a).cross(&(c - a)).norm()
+}
+
+/// Point distributed uniformly over the triangle area.
+pub(crate) fn sample([a, b, c]: [&Vector; 3], samples: &mut Samples) -> Vector {
+    let (x, y) = samples.get_2d();
+    let s = x.sqrt();
+    let u = 1. - s;
+    let v = y * s;
+    a + u * (b - a) + v * (c - a)
+}
+
+/// Solid angle density of the touching point for points sampled uniformly over `area`.
+pub(crate) fn area_pdf(from: &Vector, Touching { p, geometric_normal, .. }: &Touching, area: f64) -> f64 {
+    let to = p - from;
+    let cos = geometric_normal.dot(&to).abs() / to.norm();
+    if cos.is_nan() || cos <= 0. || area <= 0. {
+        return 0.;
+    }
+    to.norm_squared() / (cos * area)
+}
+
+pub(crate) fn bounds<'a>(vertices: impl Iterator<Item=&'a Vector>) -> Aabb {
+    vertices.fold(Aabb::empty(), |b, v| b.grow(v))
+}
+
+/// Möller–Trumbore ray-triangle intersection.
+pub(crate) fn touch(
+    r: &Ray, t_min: f64, t_max: f64,
+    [a, b, c]: [&Vector; 3],
+    normals: Option<[&NormVector; 3]>,
+    uvs: Option<[(f64, f64); 3]>,
+    material: &MaterialArc,
+) -> Option<Touching> {
+    let Ray { orig, dir } = r;
+    let e1 = b - a;
+    let e2 = c - a;
+    let p = dir.cross(&e2);
+    let det = e1.dot(&p);
+    if det.abs() < 1e-12 {
+        return None;
+    }
+
+    let inv_det = 1. / det;
+    let s = orig - a;
+    let u = s.dot(&p) * inv_det;
+    if !(0. ..=1.).contains(&u) {
+        return None;
+    }
+    let q = s.cross(&e1);
+    let v = dir.dot(&q) * inv_det;
+    if v < 0. || u + v > 1. {
+        return None;
+    }
+    let t = e2.dot(&q) * inv_det;
+    if t <= t_min || t >= t_max {
+        return None;
+    }
+
+    let w = 1. - u - v;
+    let geometric = NormVector::from(e1.cross(&e2));
+    let front_face = dir.dot(&geometric) <= 0.;
+    let normal = match normals {
+        Some([na, nb, nc]) => {
+            let shading = w * na.get() + u * nb.get() + v * nc.get();
+            // Vertex normals of meshes with inconsistent winding may face the other side.
+            let shading = if shading.dot(&geometric) < 0. { -shading } else { shading };
+            NormVector::from(shading)
+        }
+        None => geometric.clone(),
+    };
+    let uv = match uvs {
+        Some([ta, tb, tc]) => (
+            w * ta.0 + u * tb.0 + v * tc.0,
+            w * ta.1 + u * tb.1 + v * tc.1,
+        ),
+        None => (u, v),
+    };
+    let facing = |n: NormVector| if front_face { n } else { NormVector::from_unchecked(-n.get()) };
+    Some(Touching {
+        p: r.point(t),
+        t: Positive::new(t)?,
+        normal: facing(normal),
+        geometric_normal: facing(geometric),
+        front_face,
+        uv,
+        material: Arc::clone(material),
+        light: None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use approx::assert_abs_diff_eq;
+
+    use color::Color;
+
+    use super::*;
+    use crate::Lambertian;
+
+    fn triangle() -> TriangleBuilder {
+        Triangle::new()
+            .vertices(Vector::new(0., 0., -1.), Vector::new(1., 0., -1.), Vector::new(0., 1., -1.))
+            .lambertian(Lambertian::new(Color { r: 1., g: 1., b: 1. }))
+    }
+
+    fn ray(orig: Vector, dir: Vector) -> Ray {
+        Ray { orig, dir: NormVector::from(dir) }
+    }
+
+    fn touch(t: &Triangle, r: &Ray) -> Option<Touching> {
+        t.touch(r, 0.001, f64::MAX)
+    }
+
+    #[test]
+    fn front() {
+        let r = ray(Vector::new(0.25, 0.5, 0.), Vector::new(0., 0., -1.));
+        let touching = touch(&triangle().build(), &r).unwrap();
+        assert_abs_diff_eq!(touching.t.get(), 1.);
+        assert_abs_diff_eq!(touching.p, Vector::new(0.25, 0.5, -1.));
+        assert_abs_diff_eq!(*touching.normal.get(), Vector::new(0., 0., 1.));
+        assert!(touching.front_face);
+        // Barycentric weights of the second and the third vertices.
+        assert_abs_diff_eq!(touching.uv.0, 0.25);
+        assert_abs_diff_eq!(touching.uv.1, 0.5);
+    }
+
+    #[test]
+    fn back() {
+        let r = ray(Vector::new(0.25, 0.25, -2.), Vector::new(0., 0., 1.));
+        let touching = touch(&triangle().build(), &r).unwrap();
+        assert_abs_diff_eq!(*touching.normal.get(), Vector::new(0., 0., -1.));
+        assert!(!touching.front_face);
+    }
+
+    #[test]
+    fn uvs() {
+        let t = triangle().uvs((0., 0.), (1., 0.), (1., 1.)).build();
+        let r = ray(Vector::new(0.5, 0.25, 0.), Vector::new(0., 0., -1.));
+        let touching = touch(&t, &r).unwrap();
+        assert_abs_diff_eq!(touching.uv.0, 0.75);
+        assert_abs_diff_eq!(touching.uv.1, 0.25);
+    }
+
+    #[test]
+    fn edges() {
+        let t = triangle().build();
+        // On the hypotenuse and on a vertex.
+        assert!(touch(&t, &ray(Vector::new(0.5, 0.5, 0.), Vector::new(0., 0., -1.))).is_some());
+        assert!(touch(&t, &ray(Vector::new(0., 0., 0.), Vector::new(0., 0., -1.))).is_some());
+        // Just outside of each edge.
+        for (x, y) in [(0.5, -1e-9), (-1e-9, 0.5), (0.5 + 1e-9, 0.5)] {
+            assert!(touch(&t, &ray(Vector::new(x, y, 0.), Vector::new(0., 0., -1.))).is_none());
+        }
+    }
+
+    #[test]
+    fn miss() {
+        let t = triangle().build();
+        // Parallel to the plane, both outside and inside of it.
+        assert!(touch(&t, &ray(Vector::new(0.25, 0.25, 0.), Vector::new(1., 0., 0.))).is_none());
+        assert!(touch(&t, &ray(Vector::new(-1., 0.25, -1.), Vector::new(1., 0., 0.))).is_none());
+        // Behind the origin.
+        assert!(touch(&t, &ray(Vector::new(0.25, 0.25, 0.), Vector::new(0., 0., 1.))).is_none());
+    }
+
+    #[test]
+    fn shading_normal_follows_geometric_side() {
+        let flipped = NormVector::new(0.1, 0., -1.);
+        let t = triangle().normals(flipped.clone(), flipped.clone(), flipped).build();
+        let r = ray(Vector::new(0.25, 0.25, 0.), Vector::new(0., 0., -1.));
+        let touching = touch(&t, &r).unwrap();
+        assert!(touching.front_face);
+        assert!(touching.normal.z > 0.);
+        assert!(touching.normal.dot(r.dir.get()) < 0.);
+    }
+}
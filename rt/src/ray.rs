@@ -30,6 +30,6 @@ impl Ray {
     }
 
     pub(crate) fn point(&self, t: f64) -> Vector {
-        &self.orig + t * self.dir.get()
+        self.orig + t * self.dir.get()
     }
 }
@@ -1,21 +1,129 @@
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+
 use rayon::prelude::*;
+use rayon::ThreadPool;
 
-use color::Color;
+use color::{Color, Encoding, ToneMap};
 use image::Image;
 
+use crate::lights::Illumination;
 use crate::objs::Touching;
 use crate::ray::Ray;
 use crate::scene::Scene;
+use crate::sampler::{Sampler, Samples};
+use crate::tiles::{tiles, Tile, TileOrder};
+use crate::utils::clone_vec;
+
+const SELF_TOUCHING_THRESHOLD: f64 = 0.001;
 
 pub type Current = usize;
 pub type Total = usize;
+/// Called after every tile of every pass.
 pub type Logger = Box<dyn Fn(Current, Total) + Send + Sync + 'static>;
+/// Gets the tile finished out of all tiles of all passes.
+pub type TileCallback<'a> = Box<dyn Fn(&Tile, Current, Total) + Send + Sync + 'a>;
+/// Gets the image accumulated after the pass, rendering stops if it returns `false`.
+pub type PassCallback<'a> = Box<dyn Fn(&Sampled, Current, Total) -> bool + Send + Sync + 'a>;
+
+/// Auxiliary data of the first surface seen through pixel centers,
+/// pixels where rays escape get the background as albedo.
+pub struct Aovs {
+    pub albedo: Image<f32>,
+    /// World space normals facing the camera.
+    pub normal: Image<f32>,
+    /// Distances from the camera in row-major order, infinite where rays escape.
+    pub depth: Vec<f32>,
+}
+
+/// Budget of adaptive sampling, pixels get at least `min_samples` and `mean_samples` on average,
+/// stopping once the standard error of their mean luminance falls below `threshold` of it.
+/// Samples saved on converged pixels are spread over the others in extra passes.
+/// Luminance below `0.05` counts as `0.05`, so dark pixels do not get the whole budget.
+#[derive(Clone, Copy, Debug)]
+pub struct Adaptive {
+    pub min_samples: usize,
+    pub mean_samples: usize,
+    pub threshold: f64,
+}
+
+/// Radiance along with the number of samples taken through each pixel.
+pub struct Sampled {
+    /// Premultiplied by alpha if it is computed.
+    pub image: Image<f32>,
+    /// Fractions of camera rays touching the scene in row-major order.
+    pub alpha: Option<Vec<f32>>,
+    /// Samples per pixel in row-major order.
+    pub samples: Vec<usize>,
+}
+
+/// Sums of camera ray samples through a pixel.
+struct Pixel {
+    covered: Color<f64>,
+    escaped: Color<f64>,
+    hits: usize,
+    samples: usize,
+    luminance: f64,
+    luminance2: f64,
+    /// Whether adaptive sampling stopped sampling the pixel.
+    converged: bool,
+}
+
+impl Pixel {
+    fn new() -> Self {
+        Pixel {
+            covered: Color { r: 0., g: 0., b: 0. },
+            escaped: Color { r: 0., g: 0., b: 0. },
+            hits: 0,
+            samples: 0,
+            luminance: 0.,
+            luminance2: 0.,
+            converged: false,
+        }
+    }
+
+    fn add(&mut self, color: Color<f64>, hit: bool) {
+        if hit {
+            self.covered += color;
+            self.hits += 1;
+        } else {
+            self.escaped += color;
+        }
+        self.samples += 1;
+        let luminance = color.luminance();
+        self.luminance += luminance;
+        self.luminance2 += luminance * luminance;
+    }
+
+    /// Standard error of the mean luminance relative to it.
+    fn relative_error(&self) -> f64 {
+        const DARK: f64 = 0.05;
+        let n = self.samples as f64;
+        let mean = self.luminance / n;
+        let variance = ((self.luminance2 - n * mean * mean) / (n - 1.)).max(0.);
+        (variance / n).sqrt() / mean.max(DARK)
+    }
+}
 
 pub struct Render<'a> {
     scene: &'a Scene,
     logger: Logger,
+    on_tile: TileCallback<'a>,
+    tile_size: usize,
+    tile_order: TileOrder,
+    thread_pool: Option<&'a ThreadPool>,
+    pass_samples: Option<usize>,
+    on_pass: PassCallback<'a>,
     samples_per_pixel: usize,
+    adaptive: Option<Adaptive>,
     diffuse_depth: usize,
+    roulette_depth: Option<usize>,
+    light_sampling: bool,
+    sampler: Sampler,
+    seed: u64,
+    tone_map: ToneMap,
+    exposure: f64,
+    encoding: Encoding,
 }
 
 impl<'a> Render<'a> {
@@ -23,8 +131,22 @@ impl<'a> Render<'a> {
         Render {
             scene,
             logger: Box::new(|_, _| {}),
+            on_tile: Box::new(|_, _, _| {}),
+            tile_size: 16,
+            tile_order: TileOrder::default(),
+            thread_pool: None,
+            pass_samples: None,
+            on_pass: Box::new(|_, _, _| true),
             samples_per_pixel: 1,
+            adaptive: None,
             diffuse_depth: 1,
+            roulette_depth: Some(3),
+            light_sampling: true,
+            sampler: Sampler::default(),
+            seed: 0,
+            tone_map: ToneMap::default(),
+            exposure: 0.,
+            encoding: Encoding::default(),
         }
     }
 
@@ -33,72 +155,652 @@ impl<'a> Render<'a> {
         self
     }
 
+    /// Called after every tile along with the logger.
+    pub fn on_tile(mut self, callback: TileCallback<'a>) -> Self {
+        self.on_tile = callback;
+        self
+    }
+
+    /// Width and height of square tiles rendered by threads, 16 by default.
+    pub fn tile_size(mut self, size: usize) -> Self {
+        self.tile_size = size;
+        self
+    }
+
+    /// Order of tiles picked up by threads, spiral by default.
+    pub fn tile_order(mut self, order: TileOrder) -> Self {
+        self.tile_order = order;
+        self
+    }
+
+    /// Pool of rendering threads, the global rayon pool by default.
+    pub fn thread_pool(mut self, pool: &'a ThreadPool) -> Self {
+        self.thread_pool = Some(pool);
+        self
+    }
+
+    /// Renders in passes adding `n` samples to each pixel, with `on_pass` called after each of them.
+    pub fn progressive(mut self, n: Option<usize>) -> Self {
+        self.pass_samples = n;
+        self
+    }
+
+    /// Called after every pass, the logger counts tiles of all passes.
+    /// Adaptive sampling adds passes when pixels converge, so their total may grow.
+    pub fn on_pass(mut self, callback: PassCallback<'a>) -> Self {
+        self.on_pass = callback;
+        self
+    }
+
+    /// Panics if `n` is zero.
     pub fn samples_per_pixel(mut self, n: usize) -> Self {
+        assert!(n > 0, "Samples per pixel should be positive");
         self.samples_per_pixel = n;
         self
     }
 
+    /// Replaces the fixed `samples_per_pixel` by the adaptive budget.
+    pub fn adaptive(mut self, adaptive: Option<Adaptive>) -> Self {
+        self.adaptive = adaptive;
+        self
+    }
+
     pub fn diffuse_depth(mut self, n: usize) -> Self {
         self.diffuse_depth = n;
         self
     }
 
+    /// Number of bounces after which paths are randomly terminated by Russian roulette
+    /// with probability falling with their throughput, 3 by default, `None` disables it.
+    /// Survived paths are reweighted, so `diffuse_depth` is the only source of bias.
+    pub fn roulette_depth(mut self, depth: Option<usize>) -> Self {
+        self.roulette_depth = depth;
+        self
+    }
+
+    /// Whether to sample emissive objects directly at diffuse bounces, enabled by default.
+    /// Both strategies are combined by multiple importance sampling.
+    pub fn light_sampling(mut self, enabled: bool) -> Self {
+        self.light_sampling = enabled;
+        self
+    }
+
+    /// Source of values of pixel positions and random decisions, Sobol by default.
+    pub fn sampler(mut self, sampler: Sampler) -> Self {
+        self.sampler = sampler;
+        self
+    }
+
+    /// Seed of the random decisions, the same seed gives the same image
+    /// regardless of the number of threads.
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    /// Operator used by `render` to fit radiance into the displayable range, clamping by default.
+    pub fn tone_map(mut self, tone_map: ToneMap) -> Self {
+        self.tone_map = tone_map;
+        self
+    }
+
+    /// Exposure compensation of `render` in stops.
+    pub fn exposure(mut self, exposure: f64) -> Self {
+        self.exposure = exposure;
+        self
+    }
+
+    /// Transfer function used by `render` to quantize radiance, sRGB by default.
+    pub fn encoding(mut self, encoding: Encoding) -> Self {
+        self.encoding = encoding;
+        self
+    }
+
     pub fn render(&self) -> Image {
+        self.render_hdr()
+            .tone_mapped(self.tone_map, self.exposure)
+            .to_ldr(self.encoding)
+    }
+
+    /// Linear radiance of pixels, `1.0` stands for the white background.
+    pub fn render_hdr(&self) -> Image<f32> {
+        self.render_sampled(false).image
+    }
+
+    /// Radiance with transparent background, premultiplied by alpha.
+    /// Alpha values are fractions of camera rays touching the scene in row-major order.
+    pub fn render_rgba(&self) -> (Image<f32>, Vec<f32>) {
+        let Sampled { image, alpha, .. } = self.render_sampled(true);
+        (image, alpha.unwrap())
+    }
+
+    /// Linear radiance, with transparent background if `alpha` is set.
+    pub fn render_sampled(&self, alpha: bool) -> Sampled {
+        let height = self.scene.height.get();
+        let width = self.scene.width.get();
+        let (_, mean) = self.sample_budget();
+        let pass = self.pass_samples.unwrap_or(mean).max(1);
+        let tiles = tiles(width, height, self.tile_size, self.tile_order);
+        // Pixels of each tile in row-major order.
+        let mut pixels: Vec<Vec<Pixel>> = tiles
+            .iter()
+            .map(|tile| (0..tile.width * tile.height).map(|_| Pixel::new()).collect())
+            .collect();
+        // Samples left to take and pixels still taking them.
+        let mut budget = mean * width * height;
+        let mut active = width * height;
+        let mut passes = remaining_passes(budget, active, pass);
+        let mut i_pass = 0;
+        loop {
+            // Pixels get equal shares, so the image does not depend on the order of tiles.
+            let n = pass.min(budget / active);
+            // Threads take tiles one by one, so they finish close to the order.
+            let queue = Mutex::new(tiles.iter().zip(pixels.iter_mut()));
+            let done = AtomicUsize::new(i_pass * tiles.len());
+            let total = passes * tiles.len();
+            self.install(|| rayon::scope(|s| {
+                for _ in 0..rayon::current_num_threads() {
+                    s.spawn(|_| loop {
+                        let next = queue.lock().unwrap().next();
+                        let Some((tile, pixels)) = next else { break };
+                        for (i, pixel) in pixels.iter_mut().enumerate() {
+                            let i_row = tile.y + i / tile.width;
+                            let i_col = tile.x + i % tile.width;
+                            self.render_pixel(pixel, i_row, i_col, n);
+                        }
+                        let current = done.fetch_add(1, Ordering::SeqCst);
+                        (self.logger)(current, total);
+                        (self.on_tile)(tile, current, total);
+                    });
+                }
+            }));
+            budget = mean * width * height - pixels.iter().flatten().map(|p| p.samples).sum::<usize>();
+            active = pixels.iter().flatten().filter(|p| !p.converged).count();
+            passes = i_pass + 1 + remaining_passes(budget, active, pass);
+            let sampled = resolve(&tiles, &pixels, width, height, alpha);
+            let stop = !(self.on_pass)(&sampled, i_pass, passes);
+            i_pass += 1;
+            if stop || i_pass == passes {
+                break sampled;
+            }
+        }
+    }
+
+    pub fn render_aovs(&self) -> Aovs {
         let height = self.scene.height.get();
         let width = self.scene.width.get();
-        let mut image = vec![Vec::new(); height];
-        image
-            .par_iter_mut()
-            .enumerate()
-            .for_each(|(i_row, row)| {
-                *row = self.render_row(i_row, height, width);
-                (self.logger)(i_row, height);
-            });
-        Image::from(image)
-    }
-
-    fn render_row(&self, i_row: usize, height: usize, width: usize) -> Vec<image::Color> {
-        let mut row = Vec::with_capacity(width);
-        for i_col in 0..width {
-            let mut color = Color { r: 0., g: 0., b: 0. };
-            for _ in 0..self.samples_per_pixel {
-                let h = (i_row as f64 + rand::random::<f64>() - 0.5) / (height - 1) as f64;
-                let w = (i_col as f64 + rand::random::<f64>() - 0.5) / (width - 1) as f64;
-                let ray = Ray::from_cam(&self.scene.cam, w, h);
-                color += self.trace(&ray, self.diffuse_depth);
+        let rows: Vec<_> = self.install(|| (0..height)
+            .into_par_iter()
+            .map(|i_row| {
+                let mut albedo = Vec::with_capacity(width);
+                let mut normal = Vec::with_capacity(width);
+                let mut depth = Vec::with_capacity(width);
+                for i_col in 0..width {
+                    let ray = self.camera_ray(i_row, i_col, 0.5, 0.5);
+                    let black = Color { r: 0., g: 0., b: 0. };
+                    if let Some(touching) = self.touch_all(&ray) {
+                        let a = touching.material.albedo(&touching);
+                        let n = touching.normal.get();
+                        albedo.push(Color::from(a));
+                        normal.push(Color { r: n.x as f32, g: n.y as f32, b: n.z as f32 });
+                        depth.push(touching.t.get() as f32);
+                    } else {
+                        let bg = (self.scene.background_getter)(&ray);
+                        albedo.push(Color::from(bg));
+                        normal.push(Color::from(black));
+                        depth.push(f32::INFINITY);
+                    }
+                }
+                (albedo, normal, depth)
+            })
+            .collect());
+        let mut albedo = Vec::with_capacity(height);
+        let mut normal = Vec::with_capacity(height);
+        let mut depth = Vec::with_capacity(width * height);
+        for (a, n, d) in rows {
+            albedo.push(a);
+            normal.push(n);
+            depth.extend(d);
+        }
+        Aovs {
+            albedo: Image::from(albedo),
+            normal: Image::from(normal),
+            depth,
+        }
+    }
+
+    /// Runs `f` in the thread pool of the render.
+    fn install<R: Send>(&self, f: impl FnOnce() -> R + Send) -> R {
+        match self.thread_pool {
+            Some(pool) => pool.install(f),
+            None => f(),
+        }
+    }
+
+    /// Ray through the point of the pixel at the offset `(dw, dh)` from its top left corner.
+    fn camera_ray(&self, i_row: usize, i_col: usize, dw: f64, dh: f64) -> Ray {
+        let h = (i_row as f64 + dh) / self.scene.height.get() as f64;
+        let w = (i_col as f64 + dw) / self.scene.width.get() as f64;
+        Ray::from_cam(&self.scene.cam, w, h)
+    }
+
+    /// Minimal and average numbers of samples per pixel.
+    fn sample_budget(&self) -> (usize, usize) {
+        match self.adaptive {
+            Some(Adaptive { min_samples, mean_samples, .. }) => {
+                let min = min_samples.max(2);
+                (min, mean_samples.max(min))
             }
-            let k = 1. / self.samples_per_pixel as f64;
-            row.push(Color::from(k * color));
+            None => (self.samples_per_pixel, self.samples_per_pixel),
         }
-        row
     }
 
-    fn trace(&self, r: &Ray, depth: usize) -> Color<f64> {
-        if depth == 0 { return Color { r: 0., g: 0., b: 0. }; }
-        if let Some(touching) = self.touch_all(r) {
-            if let Some(scatter) = touching.material.scatter(r, &touching) {
-                let a: Color<f64> = Color::from(scatter.attenuation);
-                (1. / u8::MAX as f64) * a * self.trace(&scatter.scattered, depth - 1)
+    /// Adds `n` samples unless the pixel converges.
+    fn render_pixel(&self, pixel: &mut Pixel, i_row: usize, i_col: usize, n: usize) {
+        let width = self.scene.width.get();
+        let (min, mean) = self.sample_budget();
+        for i_sample in pixel.samples..pixel.samples + n {
+            if pixel.converged {
+                return;
+            }
+            let mut samples = Samples::new(self.sampler, self.seed, i_row * width + i_col, i_sample, mean);
+            let (dw, dh) = samples.pixel();
+            let ray = self.camera_ray(i_row, i_col, dw, dh);
+            if let Some(touching) = self.touch_all(&ray) {
+                pixel.add(self.shade(ray, touching, &mut samples), true);
             } else {
-                Color { r: 0., g: 0., b: 0. }
+                pixel.add((self.scene.background_getter)(&ray), false);
+            }
+            // Checking in batches makes stopping less sensitive to lucky runs of samples.
+            if let Some(adaptive) = &self.adaptive {
+                let batch_done = pixel.samples >= min && pixel.samples.is_multiple_of(min);
+                pixel.converged = batch_done && pixel.relative_error() < adaptive.threshold;
             }
-        } else {
-            Color::from((self.scene.background_getter)(r))
         }
     }
 
-    fn touch_all(&self, r: &Ray) -> Option<Touching> {
-        const SELF_TOUCHING_THRESHOLD: f64 = 0.001;
-        let mut t_min = f64::MAX;
-        let mut res = None;
-        for obj in &self.scene.objs {
-            if let Some(touching) = obj.touch(r) {
-                if touching.t.get() < t_min && touching.t.get() > SELF_TOUCHING_THRESHOLD {
-                    t_min = touching.t.get();
-                    res = Some(touching);
+    /// Radiance arriving along the camera ray from the touched surface.
+    fn shade(&self, mut ray: Ray, mut touching: Touching, samples: &mut Samples) -> Color<f64> {
+        let mut color = Color { r: 0., g: 0., b: 0. };
+        let mut throughput = Color { r: 1., g: 1., b: 1. };
+        // Density of the ray direction, `None` for camera rays and specular bounces.
+        let mut pdf = None;
+        for depth in (1..=self.diffuse_depth).rev() {
+            samples.start_bounce(self.diffuse_depth - depth);
+            let emitted = touching.material.emitted(&touching);
+            color += throughput * (self.emission_weight(&ray, &touching, pdf) * emitted);
+            let scatter = match touching.material.scatter(&ray, &touching, samples) {
+                Some(scatter) => scatter,
+                None => break,
+            };
+            if scatter.pdf.is_some() {
+                let mut direct = self.delta_lights(&touching);
+                // Weights of sampled lights count on scattering reaching them too,
+                // which does not happen after the last bounce.
+                if self.light_sampling && depth > 1 {
+                    direct += self.sample_light(&touching, samples);
                 }
+                color += throughput * direct;
+            }
+            throughput = throughput * scatter.attenuation;
+            if depth == 1 {
+                break;
+            }
+            let bounces = self.diffuse_depth - depth + 1;
+            if self.roulette_depth.is_some_and(|min| bounces >= min) {
+                let survival = throughput.r.max(throughput.g).max(throughput.b).min(1.);
+                if survival <= samples.get_1d() {
+                    break;
+                }
+                throughput = (1. / survival) * throughput;
+            }
+            ray = scatter.scattered;
+            pdf = scatter.pdf;
+            touching = match self.touch_all(&ray) {
+                Some(touching) => touching,
+                None => {
+                    color += throughput * (self.scene.background_getter)(&ray);
+                    break;
+                }
+            };
+        }
+        color
+    }
+
+    /// Weight of the emission found by scattering with density `pdf`
+    /// against sampling the touched light directly.
+    fn emission_weight(&self, r: &Ray, touching: &Touching, pdf: Option<f64>) -> f64 {
+        match (self.light_sampling, pdf, touching.light) {
+            (true, Some(pdf), Some(i)) => {
+                let light_pdf = self.scene.lights[i].pdf(&r.orig, touching) / self.scene.lights.len() as f64;
+                power_heuristic(pdf, light_pdf)
+            }
+            _ => 1.,
+        }
+    }
+
+    /// Radiance of a random light reaching the viewer through the touching.
+    fn sample_light(&self, touching: &Touching, samples: &mut Samples) -> Color<f64> {
+        let black = Color { r: 0., g: 0., b: 0. };
+        let lights = &self.scene.lights;
+        if lights.is_empty() { return black; }
+        let i = ((samples.get_1d() * lights.len() as f64) as usize).min(lights.len() - 1);
+        let ray = Ray { orig: clone_vec(&touching.p), dir: lights[i].sample(&touching.p, samples) };
+        let light = match self.touch_all(&ray) {
+            Some(light) if light.light == Some(i) => light,
+            _ => return black,
+        };
+        let light_pdf = lights[i].pdf(&ray.orig, &light) / lights.len() as f64;
+        if !(light_pdf > 0. && light_pdf.is_finite()) { return black; }
+        let pdf = touching.material.pdf(touching, &ray.dir);
+        let weight = power_heuristic(light_pdf, pdf) / light_pdf;
+        weight * (touching.material.bsdf(touching, &ray.dir) * light.material.emitted(&light))
+    }
+
+    /// Radiance of all delta lights reaching the viewer through the touching.
+    fn delta_lights(&self, touching: &Touching) -> Color<f64> {
+        let mut color = Color { r: 0., g: 0., b: 0. };
+        for light in &self.scene.delta_lights {
+            let Some(Illumination { dir, dist, irradiance }) = light.illuminate(&touching.p) else {
+                continue;
+            };
+            let bsdf = touching.material.bsdf(touching, &dir);
+            let shadow = Ray { orig: clone_vec(&touching.p), dir };
+            if self.scene.objs.touch(&shadow, SELF_TOUCHING_THRESHOLD, dist).is_none() {
+                color += bsdf * irradiance;
+            }
+        }
+        color
+    }
+
+    fn touch_all(&self, r: &Ray) -> Option<Touching> {
+        self.scene.objs.touch(r, SELF_TOUCHING_THRESHOLD, f64::MAX)
+    }
+}
+
+/// Averages of the pixel samples of the tiles.
+fn resolve(tiles: &[Tile], pixels: &[Vec<Pixel>], width: usize, height: usize, alpha: bool) -> Sampled {
+    let mut image = vec![vec![Color { r: 0., g: 0., b: 0. }; width]; height];
+    let mut hits = vec![0.; width * height];
+    let mut samples = vec![0; width * height];
+    for (tile, pixels) in tiles.iter().zip(pixels) {
+        for (i, p) in pixels.iter().enumerate() {
+            let i_row = tile.y + i / tile.width;
+            let i_col = tile.x + i % tile.width;
+            let k = 1. / p.samples as f64;
+            let mut color = p.covered;
+            if !alpha {
+                color += p.escaped;
             }
+            image[i_row][i_col] = Color::from(k * color);
+            hits[i_row * width + i_col] = (p.hits as f64 / p.samples as f64) as f32;
+            samples[i_row * width + i_col] = p.samples;
+        }
+    }
+    let alpha = if alpha { Some(hits) } else { None };
+    Sampled { image: Image::from(image), alpha, samples }
+}
+
+/// Number of passes of `pass` samples spending the budget on the active pixels,
+/// assuming none of them converges.
+fn remaining_passes(budget: usize, active: usize, pass: usize) -> usize {
+    if active == 0 || budget < active { 0 } else { (budget / active).div_ceil(pass) }
+}
+
+/// Weight of a sample drawn with density `pdf` combined with the `other` strategy.
+fn power_heuristic(pdf: f64, other: f64) -> f64 {
+    let (a, b) = (pdf * pdf, other * other);
+    if a + b > 0. { a / (a + b) } else { 0. }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::num::NonZeroUsize;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use super::*;
+    use crate::{
+        Camera, DiffuseLight, Lambertian, NormVector, PointLight, Positive, SceneBuilder, Sphere, Triangle, Vector,
+    };
+
+    fn scene() -> Scene {
+        sized(16, 12)
+    }
+
+    /// Camera looking at the gray floor.
+    fn floor(width: usize, height: usize) -> SceneBuilder {
+        Scene::new()
+            .width(NonZeroUsize::new(width).unwrap())
+            .height(NonZeroUsize::new(height).unwrap())
+            .cam(Camera {
+                pos: Vector::new(0., 0., 3.),
+                up: NormVector::new(0., 1., 0.),
+                to: Vector::new(0., 0., 0.),
+                vfov: Positive::new(60.).unwrap(),
+                aspect_ratio: Positive::new(4. / 3.).unwrap(),
+            })
+            .add_sphere(Sphere::new()
+                .center(Vector::new(0., -100.5, 0.))
+                .radius(Positive::new(100.).unwrap())
+                .lambertian(Lambertian::new(Color { r: 0.5, g: 0.5, b: 0.5 }))
+                .build())
+    }
+
+    fn sized(width: usize, height: usize) -> Scene {
+        floor(width, height)
+            .add_sphere(Sphere::new()
+                .center(Vector::new(0., 1., 0.))
+                .radius(Positive::new(0.2).unwrap())
+                .diffuse_light(DiffuseLight { emit: Color { r: 4., g: 4., b: 4. } })
+                .build())
+            .background_getter(Box::new(|_| Color { r: 0.2, g: 0.2, b: 0.2 }))
+            .build()
+    }
+
+    fn render(scene: &Scene, seed: u64, threads: usize) -> Image<f32> {
+        let pool = rayon::ThreadPoolBuilder::new().num_threads(threads).build().unwrap();
+        let render = Render::new(scene).samples_per_pixel(4).diffuse_depth(4).seed(seed).thread_pool(&pool);
+        render.render_hdr()
+    }
+
+    fn bits(image: &Image<f32>) -> Vec<[u32; 3]> {
+        image.rows().flatten().map(|c| [c.r.to_bits(), c.g.to_bits(), c.b.to_bits()]).collect()
+    }
+
+    #[test]
+    fn same_seed_same_image() {
+        let scene = scene();
+        assert_eq!(bits(&render(&scene, 7, 1)), bits(&render(&scene, 7, 4)));
+    }
+
+    #[test]
+    fn tiles_do_not_change_image() {
+        let scene = scene();
+        let render = |size, order| Render::new(&scene).samples_per_pixel(4).tile_size(size).tile_order(order);
+        let tiles = AtomicUsize::new(0);
+        let hilbert = render(5, TileOrder::Hilbert)
+            .on_tile(Box::new(|_, _, total| {
+                assert_eq!(total, 12);
+                tiles.fetch_add(1, Ordering::SeqCst);
+            }))
+            .render_hdr();
+        assert_eq!(tiles.load(Ordering::SeqCst), 12);
+        assert_eq!(bits(&render(1, TileOrder::Scanline).render_hdr()), bits(&hilbert));
+    }
+
+    #[test]
+    fn passes_add_up() {
+        let scene = scene();
+        let whole = Render::new(&scene).samples_per_pixel(8).render_hdr();
+        let passes = AtomicUsize::new(0);
+        let progressive = Render::new(&scene)
+            .samples_per_pixel(8)
+            .progressive(Some(3))
+            .on_pass(Box::new(|_, _, total| {
+                assert_eq!(total, 3);
+                passes.fetch_add(1, Ordering::SeqCst);
+                true
+            }))
+            .render_hdr();
+        assert_eq!(passes.load(Ordering::SeqCst), 3);
+        assert_eq!(bits(&whole), bits(&progressive));
+    }
+
+    #[test]
+    fn stop_after_pass() {
+        let scene = scene();
+        let sampled = Render::new(&scene)
+            .samples_per_pixel(8)
+            .progressive(Some(2))
+            .on_pass(Box::new(|_, pass, _| pass < 1))
+            .render_sampled(false);
+        assert!(sampled.samples.iter().all(|n| *n == 4));
+    }
+
+    #[test]
+    fn different_seeds_differ() {
+        let scene = scene();
+        assert_ne!(bits(&render(&scene, 7, 2)), bits(&render(&scene, 8, 2)));
+    }
+
+    #[test]
+    fn single_pixel_sides() {
+        for (width, height) in [(1, 1), (1, 12), (16, 1)] {
+            let scene = sized(width, height);
+            let image = Render::new(&scene).samples_per_pixel(2).render_hdr();
+            assert!(image.rows().flatten().all(|c| c.r.is_finite() && c.g.is_finite() && c.b.is_finite()));
+            let aovs = Render::new(&scene).render_aovs();
+            assert_eq!(aovs.depth.len(), width * height);
+            assert!(aovs.depth.iter().all(|d| !d.is_nan()));
         }
-        res
+    }
+
+    #[test]
+    #[should_panic(expected = "Samples per pixel should be positive")]
+    fn zero_samples() {
+        Render::new(&scene()).samples_per_pixel(0);
+    }
+
+    #[test]
+    fn albedo_aov() {
+        let scene = scene();
+        let albedo = |seed| Render::new(&scene).seed(seed).render_aovs().albedo;
+        let aovs = albedo(1);
+        assert_eq!(bits(&aovs), bits(&albedo(2)));
+        let floor = aovs.rows().last().unwrap()[8];
+        assert_eq!((floor.r, floor.g, floor.b), (0.5, 0.5, 0.5));
+        // The light is white at full brightness.
+        assert!(aovs.rows().flatten().any(|c| (c.r, c.g, c.b) == (1., 1., 1.)));
+    }
+
+    fn mean(image: &Image<f32>) -> f64 {
+        let n = (image.w() * image.h()) as f64;
+        image.rows().flatten().map(|c| Color::<f64>::from(*c).luminance()).sum::<f64>() / n
+    }
+
+    /// Floor lit from above by a square of two triangles, their vertex normals
+    /// are tilted far from the face like the ones of coarse smooth meshes.
+    fn smooth_light() -> Scene {
+        let light = |a, b, c| {
+            let tilted = || NormVector::new(0.9, -0.45, 0.);
+            Triangle::new()
+                .vertices(a, b, c)
+                .normals(tilted(), tilted(), tilted())
+                .diffuse_light(DiffuseLight { emit: Color { r: 4., g: 4., b: 4. } })
+                .build()
+        };
+        floor(16, 12)
+            .background_getter(Box::new(|_| Color { r: 0., g: 0., b: 0. }))
+            .add_triangle(light(Vector::new(-0.5, 2., -0.5), Vector::new(0.5, 2., -0.5), Vector::new(0.5, 2., 0.5)))
+            .add_triangle(light(Vector::new(-0.5, 2., -0.5), Vector::new(0.5, 2., 0.5), Vector::new(-0.5, 2., 0.5)))
+            .build()
+    }
+
+    #[test]
+    fn light_sampling_is_unbiased() {
+        let scene = smooth_light();
+        let render = |enabled| Render::new(&scene)
+            .samples_per_pixel(256)
+            .diffuse_depth(2)
+            .light_sampling(enabled)
+            .render_hdr();
+        let (sampled, scattered) = (mean(&render(true)), mean(&render(false)));
+        assert!(sampled > 0.);
+        assert!((sampled / scattered - 1.).abs() < 0.05, "{} != {}", sampled, scattered);
+    }
+
+    #[test]
+    fn delta_light_shadows() {
+        // The floor seen through the pixel lies in front of the point right under the light.
+        let pixel = |scene: SceneBuilder| {
+            let scene = scene
+                .background_getter(Box::new(|_| Color { r: 0., g: 0., b: 0. }))
+                .add_point_light(PointLight {
+                    position: Vector::new(0., 2., 0.),
+                    intensity: Color { r: 4., g: 4., b: 4. },
+                })
+                .build();
+            // Surfaces seen by the camera are the last bounce.
+            let image = Render::new(&scene).samples_per_pixel(4).diffuse_depth(1).render_hdr();
+            image[(8, 8)].r
+        };
+        assert!(pixel(floor(16, 12)) > 0.);
+        let blocked = floor(16, 12).add_sphere(Sphere::new()
+            .center(Vector::new(0., 1., 0.))
+            .radius(Positive::new(0.6).unwrap())
+            .lambertian(Lambertian::new(Color { r: 0.5, g: 0.5, b: 0.5 }))
+            .build());
+        assert_eq!(pixel(blocked), 0.);
+    }
+
+    #[test]
+    fn roulette_is_unbiased() {
+        let scene = scene();
+        let render = |depth| Render::new(&scene)
+            .samples_per_pixel(128)
+            .diffuse_depth(8)
+            .roulette_depth(depth)
+            .render_hdr();
+        let (full, roulette) = (mean(&render(None)), mean(&render(Some(1))));
+        assert!((roulette / full - 1.).abs() < 0.02, "{} != {}", roulette, full);
+    }
+
+    #[test]
+    fn adaptive_budget() {
+        let scene = scene();
+        let sampled = Render::new(&scene)
+            .diffuse_depth(4)
+            .adaptive(Some(Adaptive { min_samples: 16, mean_samples: 64, threshold: 0.01 }))
+            .render_sampled(false);
+        // The top left pixel sees the flat background, the floor under the small light is noisy.
+        assert_eq!(sampled.samples[0], 16);
+        assert!(sampled.samples.iter().all(|n| *n >= 16));
+        let max = *sampled.samples.iter().max().unwrap();
+        assert!(max > 4 * 64, "{}", max);
+        // Samples saved on the background are spent on the floor, leaving less than one per pixel.
+        let total: usize = sampled.samples.iter().sum();
+        assert!(total <= 64 * 16 * 12 && total > 63 * 16 * 12, "{}", total);
+    }
+
+    #[test]
+    fn adaptive_passes() {
+        let scene = scene();
+        let passes = Mutex::new(vec![]);
+        let sampled = Render::new(&scene)
+            .diffuse_depth(4)
+            .adaptive(Some(Adaptive { min_samples: 16, mean_samples: 64, threshold: 0.01 }))
+            .progressive(Some(32))
+            .on_pass(Box::new(|sampled, pass, passes_planned| {
+                passes.lock().unwrap().push((pass, passes_planned, sampled.samples[0]));
+                true
+            }))
+            .render_sampled(false);
+        let passes = passes.into_inner().unwrap();
+        // Samples saved on the background after the first pass are planned for extra passes.
+        assert!(passes[0].1 > 2, "{:?}", passes);
+        let (last, planned, _) = *passes.last().unwrap();
+        assert_eq!(last + 1, planned);
+        assert!(passes.iter().enumerate().all(|(i, p)| p.0 == i && p.2 == 16));
+        assert!(sampled.samples.iter().sum::<usize>() <= 64 * 16 * 12);
     }
 }
@@ -0,0 +1,324 @@
+/// Way of choosing values of the random dimensions of camera rays.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum Sampler {
+    /// Independent uniform random values.
+    Random,
+    /// Values jittered inside of strata shuffled for each pixel and dimension.
+    Stratified,
+    /// Halton sequence Owen-scrambled for each pixel,
+    /// dimensions past the table of prime bases are random.
+    Halton,
+    /// Owen-scrambled Sobol sequence shuffled for each pixel and pair of dimensions.
+    #[default]
+    Sobol,
+}
+
+const PIXEL_DIM: u32 = 0;
+#[cfg(test)]
+const LENS_DIM: u32 = 2;
+#[cfg(test)]
+const TIME_DIM: u32 = 4;
+/// Lens and time dimensions are reserved for cameras with depth of field
+/// and motion blur, so adding them will not change others.
+const FIRST_BOUNCE_DIM: u32 = 5;
+/// Light sampling, scattering and Russian roulette take at most 7 dimensions,
+/// so the same dimensions serve the same bounce in all samples.
+const BOUNCE_DIMS: u32 = 8;
+
+const PRIMES: [u64; 32] = [
+    2, 3, 5, 7, 11, 13, 17, 19, 23, 29, 31, 37, 41, 43, 47, 53,
+    59, 61, 67, 71, 73, 79, 83, 89, 97, 101, 103, 107, 109, 113, 127, 131,
+];
+
+const ONE_MINUS_EPSILON: f64 = 1. - f64::EPSILON / 2.;
+
+/// Values of the dimensions of a single camera ray in `[0, 1)`,
+/// bounces consume dimensions in order.
+pub(crate) struct Samples {
+    sampler: Sampler,
+    seed: u64,
+    pixel: u64,
+    index: u32,
+    count: u32,
+    dim: u32,
+}
+
+impl Samples {
+    /// Sample `index` out of `count` through the `pixel` in row-major order.
+    pub(crate) fn new(sampler: Sampler, seed: u64, pixel: usize, index: usize, count: usize) -> Self {
+        Samples {
+            sampler,
+            seed,
+            pixel: pixel as u64,
+            index: index as u32,
+            count: count.max(1) as u32,
+            dim: FIRST_BOUNCE_DIM,
+        }
+    }
+
+    /// Position inside of the pixel.
+    pub(crate) fn pixel(&self) -> (f64, f64) {
+        self.value_2d(PIXEL_DIM)
+    }
+
+    /// Position on the lens.
+    #[cfg(test)]
+    pub(crate) fn lens(&self) -> (f64, f64) {
+        self.value_2d(LENS_DIM)
+    }
+
+    /// Moment inside of the shutter interval.
+    #[cfg(test)]
+    pub(crate) fn time(&self) -> f64 {
+        self.value_1d(TIME_DIM)
+    }
+
+    /// Switches to dimensions of the bounce with the index.
+    pub(crate) fn start_bounce(&mut self, bounce: usize) {
+        self.dim = FIRST_BOUNCE_DIM + bounce as u32 * BOUNCE_DIMS;
+    }
+
+    pub(crate) fn get_1d(&mut self) -> f64 {
+        let x = self.value_1d(self.dim);
+        self.dim += 1;
+        x
+    }
+
+    pub(crate) fn get_2d(&mut self) -> (f64, f64) {
+        let xy = self.value_2d(self.dim);
+        self.dim += 2;
+        xy
+    }
+
+    fn value_1d(&self, dim: u32) -> f64 {
+        match self.sampler {
+            Sampler::Random => self.random(dim, 0),
+            Sampler::Stratified => {
+                let stratum = permute(self.index, self.count, self.hash(dim, 1) as u32);
+                ((stratum as f64 + self.random(dim, 2)) / self.count as f64).min(ONE_MINUS_EPSILON)
+            }
+            Sampler::Halton => self.halton(dim),
+            Sampler::Sobol => {
+                let index = owen_scramble(self.index, self.hash(dim, 3) as u32);
+                to_unit(owen_scramble(index.reverse_bits(), self.hash(dim, 4) as u32))
+            }
+        }
+    }
+
+    fn value_2d(&self, dim: u32) -> (f64, f64) {
+        match self.sampler {
+            Sampler::Random | Sampler::Halton => (self.value_1d(dim), self.value_1d(dim + 1)),
+            Sampler::Stratified => {
+                let nx = (self.count as f64).sqrt().ceil() as u32;
+                let ny = self.count.div_ceil(nx);
+                let stratum = permute(self.index, nx * ny, self.hash(dim, 1) as u32);
+                (
+                    (((stratum % nx) as f64 + self.random(dim, 2)) / nx as f64).min(ONE_MINUS_EPSILON),
+                    (((stratum / nx) as f64 + self.random(dim + 1, 2)) / ny as f64).min(ONE_MINUS_EPSILON),
+                )
+            }
+            Sampler::Sobol => {
+                let index = owen_scramble(self.index, self.hash(dim, 3) as u32);
+                (
+                    to_unit(owen_scramble(index.reverse_bits(), self.hash(dim, 4) as u32)),
+                    to_unit(owen_scramble(sobol_second(index), self.hash(dim + 1, 4) as u32)),
+                )
+            }
+        }
+    }
+
+    fn halton(&self, dim: u32) -> f64 {
+        match PRIMES.get(dim as usize) {
+            Some(base) => {
+                owen_radical_inverse(*base, self.index as u64, self.hash(dim, 5))
+            }
+            None => self.random(dim, 0),
+        }
+    }
+
+    /// Uniform value depending on the seed, pixel, sample, dimension and `salt`.
+    fn random(&self, dim: u32, salt: u64) -> f64 {
+        let h = mix(self.hash(dim, salt) ^ mix(self.index as u64));
+        (h >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    fn hash(&self, dim: u32, salt: u64) -> u64 {
+        mix(mix(mix(self.seed) ^ self.pixel) ^ ((dim as u64) << 8 | salt))
+    }
+}
+
+/// Finalizer of SplitMix64.
+fn mix(mut x: u64) -> u64 {
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^ (x >> 31)
+}
+
+fn to_unit(x: u32) -> f64 {
+    x as f64 / (1u64 << 32) as f64
+}
+
+/// Digits of `i` in the base mirrored around the point, each permuted
+/// depending on the more significant digits, so any scrambled point stays uniform.
+fn owen_radical_inverse(base: u64, mut i: u64, seed: u64) -> f64 {
+    let inv_base = 1. / base as f64;
+    let mut scale = 1.;
+    let mut reversed = 0.;
+    let mut prefix = 0u64;
+    // Digits past the last one of `i` are zeros which get scrambled too.
+    while 1. - (base - 1) as f64 * scale < 1. {
+        let digit = i % base;
+        let permuted = permute(digit as u32, base as u32, mix(seed ^ prefix) as u32);
+        scale *= inv_base;
+        reversed += permuted as f64 * scale;
+        prefix = prefix.wrapping_mul(base).wrapping_add(digit + 1);
+        i /= base;
+    }
+    reversed.min(ONE_MINUS_EPSILON)
+}
+
+/// Second dimension of the Sobol sequence as a 32-bit fraction.
+fn sobol_second(mut index: u32) -> u32 {
+    let mut x = 0;
+    let mut v = 1 << 31;
+    while index != 0 {
+        if index & 1 != 0 {
+            x ^= v;
+        }
+        index >>= 1;
+        v ^= v >> 1;
+    }
+    x
+}
+
+/// Nested uniform scrambling of bits of a 32-bit fraction by the hash of Laine and Karras
+/// improved by Burley.
+fn owen_scramble(x: u32, seed: u32) -> u32 {
+    let mut x = x.reverse_bits();
+    x = x.wrapping_add(seed);
+    x ^= x.wrapping_mul(0x6c50_b47c);
+    x ^= x.wrapping_mul(0xb82f_1e52);
+    x ^= x.wrapping_mul(0xc7af_e638);
+    x ^= x.wrapping_mul(0x8d22_f6e6);
+    x.reverse_bits()
+}
+
+/// Kensler's permutation of `[0, len)` selected by the seed `p`.
+fn permute(mut i: u32, len: u32, p: u32) -> u32 {
+    let mut w = len - 1;
+    w |= w >> 1;
+    w |= w >> 2;
+    w |= w >> 4;
+    w |= w >> 8;
+    w |= w >> 16;
+    loop {
+        i ^= p;
+        i = i.wrapping_mul(0xe170_893d);
+        i ^= p >> 16;
+        i ^= (i & w) >> 4;
+        i ^= p >> 8;
+        i = i.wrapping_mul(0x0929_eb3f);
+        i ^= p >> 23;
+        i ^= (i & w) >> 1;
+        i = i.wrapping_mul(1 | p >> 27);
+        i = i.wrapping_mul(0x6935_fa69);
+        i ^= (i & w) >> 11;
+        i = i.wrapping_mul(0x74dc_b303);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0x9e50_1cc3);
+        i ^= (i & w) >> 2;
+        i = i.wrapping_mul(0xc860_a3df);
+        i &= w;
+        i ^= i >> 5;
+        if i < len {
+            return (i.wrapping_add(p)) % len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn values_2d(sampler: Sampler, dim: u32, count: usize) -> Vec<(f64, f64)> {
+        (0..count).map(|i| Samples::new(sampler, 7, 42, i, count).value_2d(dim)).collect()
+    }
+
+    /// Whether every cell of the `nx` by `ny` grid has exactly one point.
+    fn one_per_cell(points: &[(f64, f64)], nx: usize, ny: usize) -> bool {
+        let mut cells = vec![0; nx * ny];
+        for (x, y) in points {
+            assert!((0. ..1.).contains(x) && (0. ..1.).contains(y));
+            cells[(y * ny as f64) as usize * nx + (x * nx as f64) as usize] += 1;
+        }
+        cells.iter().all(|c| *c == 1)
+    }
+
+    #[test]
+    fn permutation() {
+        for len in [1, 2, 7, 16, 100] {
+            let mut values: Vec<_> = (0..len).map(|i| permute(i, len, 0xdead_beef)).collect();
+            values.sort_unstable();
+            assert_eq!(values, (0..len).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn stratified() {
+        for dim in [PIXEL_DIM, FIRST_BOUNCE_DIM] {
+            assert!(one_per_cell(&values_2d(Sampler::Stratified, dim, 16), 4, 4));
+        }
+    }
+
+    #[test]
+    fn sobol_is_net() {
+        for dim in [PIXEL_DIM, FIRST_BOUNCE_DIM + BOUNCE_DIMS] {
+            let points = values_2d(Sampler::Sobol, dim, 16);
+            for (nx, ny) in [(1, 16), (2, 8), (4, 4), (8, 2), (16, 1)] {
+                assert!(one_per_cell(&points, nx, ny));
+            }
+        }
+    }
+
+    #[test]
+    fn halton_is_stratified() {
+        let points: Vec<_> = (0..9)
+            .map(|i| Samples::new(Sampler::Halton, 7, 42, i, 9).value_1d(1))
+            .map(|x| (x, 0.5))
+            .collect();
+        assert!(one_per_cell(&points, 9, 1));
+    }
+
+    /// Root mean square error of estimates of the integral of `x * y` over the unit square.
+    fn lens_error(sampler: Sampler, count: usize) -> f64 {
+        let pixels = 256;
+        let sum: f64 = (0..pixels)
+            .map(|pixel| {
+                let estimate = (0..count)
+                    .map(|i| {
+                        let (x, y) = Samples::new(sampler, 7, pixel, i, count).lens();
+                        x * y
+                    })
+                    .sum::<f64>() / count as f64;
+                (estimate - 0.25).powi(2)
+            })
+            .sum();
+        (sum / pixels as f64).sqrt()
+    }
+
+    #[test]
+    fn sobol_converges_faster() {
+        let (sobol, random) = (lens_error(Sampler::Sobol, 16), lens_error(Sampler::Random, 16));
+        assert!(sobol < 0.25 * random, "{} vs {}", sobol, random);
+    }
+
+    #[test]
+    fn reserved_dimensions() {
+        let samples: Vec<_> = (0..16).map(|i| Samples::new(Sampler::Sobol, 7, 42, i, 16)).collect();
+        let lens: Vec<_> = samples.iter().map(Samples::lens).collect();
+        assert!(one_per_cell(&lens, 4, 4));
+        let times: Vec<_> = samples.iter().map(|s| (s.time(), 0.5)).collect();
+        assert!(one_per_cell(&times, 16, 1));
+    }
+}
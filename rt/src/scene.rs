@@ -3,9 +3,11 @@ use std::sync::Arc;
 
 use color::Color;
 
-use crate::objs::{Lambertian, Metal, Sphere, TouchBox};
+use crate::bvh::{Accel, Bvh};
+use crate::lights::{DeltaLightBox, DirectionalLight, PointLight, SpotLight};
+use crate::objs::{EmitterArc, Light, Sphere, TouchBox, Triangle, TriangleMesh};
 use crate::ray::Ray;
-use crate::utils::{NormVector, Positive, UniFloat};
+use crate::utils::{NormVector, Positive};
 use crate::Vector;
 
 #[derive(Debug)]
@@ -17,17 +19,22 @@ pub struct Camera {
     pub aspect_ratio: Positive<f64>,
 }
 
-pub type Background = Box<dyn Fn(&Ray) -> Color<u8> + Send + Sync + 'static>;
+/// Linear radiance of rays escaping the scene, `1.0` stands for white.
+pub type Background = Box<dyn Fn(&Ray) -> Color<f64> + Send + Sync + 'static>;
 
 pub struct Scene {
     pub(crate) width: NonZeroUsize,
     pub(crate) height: NonZeroUsize,
     pub(crate) cam: Camera,
-    pub(crate) objs: Vec<TouchBox>,
+    pub(crate) objs: Bvh,
+    /// Emissive objects, touchings of them refer to the indices.
+    pub(crate) lights: Vec<EmitterArc>,
+    pub(crate) delta_lights: Vec<DeltaLightBox>,
     pub(crate) background_getter: Background,
 }
 
 impl Scene {
+    #[allow(clippy::new_ret_no_self)]
     pub fn new() -> SceneBuilder {
         SceneBuilder::new()
     }
@@ -38,9 +45,18 @@ pub struct SceneBuilder {
     height: Option<NonZeroUsize>,
     cam: Option<Camera>,
     objs: Vec<TouchBox>,
+    lights: Vec<EmitterArc>,
+    delta_lights: Vec<DeltaLightBox>,
+    accel: Accel,
     background_getter: Option<Background>,
 }
 
+impl Default for SceneBuilder {
+    fn default() -> Self {
+        SceneBuilder::new()
+    }
+}
+
 impl SceneBuilder {
     pub fn new() -> Self {
         SceneBuilder {
@@ -48,6 +64,9 @@ impl SceneBuilder {
             height: None,
             cam: None,
             objs: vec![],
+            lights: vec![],
+            delta_lights: vec![],
+            accel: Accel::default(),
             background_getter: None,
         }
     }
@@ -62,16 +81,87 @@ impl SceneBuilder {
         self
     }
 
+    /// Changes the image size without distorting the picture: the missing size
+    /// follows the camera aspect ratio, otherwise the camera adopts the new one.
+    pub fn resize(mut self, width: Option<NonZeroUsize>, height: Option<NonZeroUsize>) -> Self {
+        let cam = self.cam.as_mut().unwrap();
+        let ratio = cam.aspect_ratio.get();
+        let at_least_one = |x: f64| NonZeroUsize::new((x.round() as usize).max(1)).unwrap();
+        match (width, height) {
+            (Some(w), Some(h)) => {
+                cam.aspect_ratio = Positive::new(w.get() as f64 / h.get() as f64).unwrap();
+                self.width = Some(w);
+                self.height = Some(h);
+            }
+            (Some(w), None) => {
+                self.width = Some(w);
+                self.height = Some(at_least_one(w.get() as f64 / ratio));
+            }
+            (None, Some(h)) => {
+                self.width = Some(at_least_one(h.get() as f64 * ratio));
+                self.height = Some(h);
+            }
+            (None, None) => {}
+        }
+        self
+    }
+
     pub fn cam(mut self, cam: Camera) -> Self {
         self.cam = Some(cam);
         self
     }
 
     pub fn add_sphere(mut self, sphere: Sphere) -> Self {
+        if sphere.material.is_emitter() {
+            return self.add_emitter(Arc::new(sphere));
+        }
         self.objs.push(Box::new(sphere));
         self
     }
 
+    pub fn add_triangle(mut self, triangle: Triangle) -> Self {
+        if triangle.material.is_emitter() {
+            return self.add_emitter(Arc::new(triangle));
+        }
+        self.objs.push(Box::new(triangle));
+        self
+    }
+
+    pub fn add_mesh(mut self, mesh: TriangleMesh) -> Self {
+        if mesh.is_emitter() {
+            return self.add_emitter(Arc::new(mesh));
+        }
+        self.objs.push(Box::new(mesh));
+        self
+    }
+
+    pub fn add_point_light(mut self, light: PointLight) -> Self {
+        self.delta_lights.push(Box::new(light));
+        self
+    }
+
+    pub fn add_spot_light(mut self, light: SpotLight) -> Self {
+        self.delta_lights.push(Box::new(light));
+        self
+    }
+
+    pub fn add_directional_light(mut self, light: DirectionalLight) -> Self {
+        self.delta_lights.push(Box::new(light));
+        self
+    }
+
+    fn add_emitter(mut self, emitter: EmitterArc) -> Self {
+        let index = self.lights.len();
+        self.objs.push(Box::new(Light { index, emitter: Arc::clone(&emitter) }));
+        self.lights.push(emitter);
+        self
+    }
+
+    pub fn accel(mut self, accel: Accel) -> Self {
+        self.accel = accel;
+        self
+    }
+
     pub fn background_getter(mut self, bg: Background) -> Self {
         self.background_getter = Some(bg);
         self
@@ -82,7 +172,9 @@ impl SceneBuilder {
             width: self.width.unwrap(),
             height: self.height.unwrap(),
             cam: self.cam.unwrap(),
-            objs: self.objs,
+            objs: Bvh::new(self.objs, self.accel),
+            lights: self.lights,
+            delta_lights: self.delta_lights,
             background_getter: self.background_getter.unwrap(),
         }
     }
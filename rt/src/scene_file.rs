@@ -0,0 +1,651 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use serde::Deserialize;
+
+use color::Color;
+use image::Image;
+
+use crate::bvh::Accel;
+use crate::lights::{DirectionalLight, PointLight, SpotLight};
+use crate::obj::{load_obj, ObjError};
+use crate::objs::{
+    Dielectric, DiffuseLight, Lambertian, MaterialArc, Metal,
+    Sphere, Triangle, TriangleMesh,
+};
+use crate::ray::Ray;
+use crate::scene::{Background, Camera, Scene, SceneBuilder};
+use crate::textures::{Checker, ImageTexture, Noise, NoisePattern, TextureArc};
+use crate::utils::{NormVector, Positive, UniFloat};
+use crate::Vector;
+
+#[derive(Debug)]
+pub enum SceneFileError {
+    IO(PathBuf, io::Error),
+    Syntax(PathBuf, toml::de::Error),
+    Invalid(PathBuf, String),
+    Obj(ObjError),
+    Image(PathBuf, image::Error),
+}
+
+impl fmt::Display for SceneFileError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SceneFileError::IO(path, e) => write!(f, "{}: {}", path.display(), e),
+            SceneFileError::Syntax(path, e) => write!(f, "{}: {}", path.display(), e),
+            SceneFileError::Invalid(path, msg) => write!(f, "{}: {}", path.display(), msg),
+            SceneFileError::Obj(e) => write!(f, "{}", e),
+            SceneFileError::Image(path, e) => write!(f, "{}: {}", path.display(), e),
+        }
+    }
+}
+
+impl std::error::Error for SceneFileError {}
+
+impl From<ObjError> for SceneFileError {
+    fn from(e: ObjError) -> Self {
+        SceneFileError::Obj(e)
+    }
+}
+
+/// Loads TOML scene description, relative paths inside of it are resolved
+/// against the directory of the file.
+pub fn load_scene(path: &Path) -> Result<SceneBuilder, SceneFileError> {
+    let text = fs::read_to_string(path)
+        .map_err(|e| SceneFileError::IO(path.to_path_buf(), e))?;
+    parse(&text, path)
+}
+
+fn parse(text: &str, path: &Path) -> Result<SceneBuilder, SceneFileError> {
+    let desc: SceneDesc = toml::from_str(text)
+        .map_err(|e| SceneFileError::Syntax(path.to_path_buf(), e))?;
+    let dir = path.parent().unwrap_or_else(|| Path::new(""));
+    desc.build(dir)
+        .map_err(|e| e.into_scene_file_error(path))
+}
+
+/// Error inside of the description with path of the file not known yet.
+enum DescError {
+    Invalid(String),
+    Obj(ObjError),
+    Image(PathBuf, image::Error),
+}
+
+impl DescError {
+    fn into_scene_file_error(self, path: &Path) -> SceneFileError {
+        match self {
+            DescError::Invalid(msg) => SceneFileError::Invalid(path.to_path_buf(), msg),
+            DescError::Obj(e) => SceneFileError::Obj(e),
+            DescError::Image(path, e) => SceneFileError::Image(path, e),
+        }
+    }
+}
+
+fn invalid<T>(msg: String) -> Result<T, DescError> {
+    Err(DescError::Invalid(msg))
+}
+
+fn positive(x: f64, what: &str) -> Result<Positive<f64>, DescError> {
+    Positive::new(x).map_or_else(|| invalid(format!("{} should be positive, got {}", what, x)), Ok)
+}
+
+fn non_zero(x: usize, what: &str) -> Result<NonZeroUsize, DescError> {
+    NonZeroUsize::new(x).map_or_else(|| invalid(format!("{} should be positive", what)), Ok)
+}
+
+fn vector([x, y, z]: [f64; 3]) -> Vector {
+    Vector::new(x, y, z)
+}
+
+fn direction_vector(v: [f64; 3], what: &str) -> Result<NormVector, DescError> {
+    let v = vector(v);
+    if v.norm() > 0. { Ok(NormVector::from(v)) } else { invalid(format!("{} should be non-zero", what)) }
+}
+
+fn color([r, g, b]: [u8; 3]) -> image::Color {
+    Color { r, g, b }
+}
+
+fn hdr_color([r, g, b]: [f64; 3]) -> Color<f64> {
+    Color { r, g, b }
+}
+
+/// 8-bit color scaled to linear radiance.
+fn radiance(c: [u8; 3]) -> Color<f64> {
+    (1. / u8::MAX as f64) * color(c)
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct SceneDesc {
+    width: usize,
+    height: usize,
+    camera: CameraDesc,
+    #[serde(default)]
+    accel: AccelDesc,
+    #[serde(default)]
+    materials: HashMap<String, MaterialDesc>,
+    #[serde(default)]
+    objects: Vec<ObjectDesc>,
+    #[serde(default)]
+    lights: Vec<LightDesc>,
+    background: BackgroundDesc,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+struct CameraDesc {
+    pos: [f64; 3],
+    up: [f64; 3],
+    to: [f64; 3],
+    vfov: f64,
+    /// Ratio of the image sizes by default.
+    aspect_ratio: Option<f64>,
+}
+
+#[derive(Deserialize, Default)]
+#[serde(rename_all = "snake_case")]
+enum AccelDesc {
+    Linear,
+    #[default]
+    Bvh,
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum MaterialDesc {
+    Lambertian { albedo: TextureDesc },
+    Metal { albedo: TextureDesc, fuzz: f64 },
+    Dielectric { refraction_index: f64 },
+    DiffuseLight { emit: [f64; 3] },
+}
+
+/// Texture given as an 8-bit color or a table with the `type` key.
+/// 8-bit colors are scaled to `[0, 1]` without sRGB decoding, as they always were,
+/// so existing scenes keep their look. `solid` takes exact linear values.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum TextureDesc {
+    Color([u8; 3]),
+    Typed(TypedTextureDesc),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum TypedTextureDesc {
+    /// Linear color, components may exceed 1.
+    Solid { color: [f64; 3] },
+    /// Cubes with side `scale` of alternating textures.
+    Checker { even: Box<TextureDesc>, odd: Box<TextureDesc>, scale: f64 },
+    /// Image mapped onto uv coordinates, sRGB unless it is `.hdr` or `.pfm`.
+    Image { path: PathBuf },
+    /// Procedural pattern blending linear `low` and `high` colors.
+    Noise {
+        pattern: NoisePatternDesc,
+        #[serde(default)]
+        seed: u64,
+        scale: Option<f64>,
+        octaves: Option<usize>,
+        low: Option<[f64; 3]>,
+        high: Option<[f64; 3]>,
+    },
+}
+
+#[derive(Deserialize)]
+#[serde(rename_all = "snake_case")]
+enum NoisePatternDesc {
+    Fbm,
+    Turbulence,
+    Marble,
+    Wood,
+}
+
+/// Material given inline or by the name from the `materials` table.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum MaterialRef {
+    Named(String),
+    Inline(MaterialDesc),
+}
+
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum ObjectDesc {
+    Sphere {
+        center: [f64; 3],
+        radius: f64,
+        material: MaterialRef,
+    },
+    Triangle {
+        vertices: [[f64; 3]; 3],
+        normals: Option<[[f64; 3]; 3]>,
+        uvs: Option<[(f64, f64); 3]>,
+        material: MaterialRef,
+    },
+    Mesh {
+        vertices: Vec<[f64; 3]>,
+        indices: Vec<[usize; 3]>,
+        normals: Option<Vec<[f64; 3]>>,
+        uvs: Option<Vec<(f64, f64)>>,
+        material: MaterialRef,
+    },
+    /// Wavefront `.obj` model with its own materials.
+    Obj { path: PathBuf },
+}
+
+/// Lights without geometry, intensities and irradiances are linear.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum LightDesc {
+    Point { position: [f64; 3], intensity: [f64; 3] },
+    /// Angles are in degrees, the full intensity is inside of the `falloff_angle`.
+    Spot {
+        position: [f64; 3],
+        direction: [f64; 3],
+        intensity: [f64; 3],
+        cone_angle: f64,
+        falloff_angle: Option<f64>,
+    },
+    /// Light travelling along the `direction` from the infinitely far source.
+    Directional { direction: [f64; 3], irradiance: [f64; 3] },
+}
+
+/// 8-bit colors are linear radiance scaled to `[0, 1]` like the ones of textures.
+#[derive(Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum BackgroundDesc {
+    Solid { color: [u8; 3] },
+    /// Vertical blend from `bottom` to `top` color.
+    Gradient { bottom: [u8; 3], top: [u8; 3] },
+    /// Each channel of `color` fades along its own axis of the ray direction,
+    /// down to `1 - falloff` of the channel value when looking along the axis.
+    Axes { color: [u8; 3], falloff: [f64; 3] },
+    /// Equirectangular environment map with the top row looking up along the `y` axis.
+    /// Radiance `.hdr` and `.pfm` maps are linear, `.png` and `.jpg` ones are sRGB.
+    Image { path: PathBuf, intensity: Option<f64> },
+}
+
+impl SceneDesc {
+    fn build(self, dir: &Path) -> Result<SceneBuilder, DescError> {
+        let width = non_zero(self.width, "width")?;
+        let height = non_zero(self.height, "height")?;
+        let aspect_ratio = self.camera.aspect_ratio
+            .unwrap_or(self.width as f64 / self.height as f64);
+        let cam = Camera {
+            pos: vector(self.camera.pos),
+            up: direction_vector(self.camera.up, "up")?,
+            to: vector(self.camera.to),
+            vfov: positive(self.camera.vfov, "vfov")?,
+            aspect_ratio: positive(aspect_ratio, "aspect_ratio")?,
+        };
+
+        let mut materials = HashMap::new();
+        for (name, desc) in self.materials {
+            let material = desc.build(dir)
+                .map_err(|e| in_context(e, &format!("material '{}'", name)))?;
+            materials.insert(name, material);
+        }
+
+        let mut scene = Scene::new()
+            .width(width)
+            .height(height)
+            .cam(cam)
+            .accel(match self.accel {
+                AccelDesc::Linear => Accel::Linear,
+                AccelDesc::Bvh => Accel::Bvh,
+            })
+            .background_getter(self.background.build(dir)?);
+        for (i, obj) in self.objects.into_iter().enumerate() {
+            scene = obj.add(scene, &materials, dir)
+                .map_err(|e| in_context(e, &format!("object #{}", i + 1)))?;
+        }
+        for (i, light) in self.lights.into_iter().enumerate() {
+            scene = light.add(scene)
+                .map_err(|e| in_context(e, &format!("light #{}", i + 1)))?;
+        }
+        Ok(scene)
+    }
+}
+
+fn in_context(e: DescError, context: &str) -> DescError {
+    match e {
+        DescError::Invalid(msg) => DescError::Invalid(format!("{}: {}", context, msg)),
+        e => e,
+    }
+}
+
+impl MaterialDesc {
+    fn build(self, dir: &Path) -> Result<MaterialArc, DescError> {
+        Ok(match self {
+            MaterialDesc::Lambertian { albedo } => Arc::new(Lambertian { albedo: albedo.build(dir)? }),
+            MaterialDesc::Metal { albedo, fuzz } => Arc::new(Metal {
+                albedo: albedo.build(dir)?,
+                fuzz: UniFloat::new(fuzz).map_or_else(
+                    || invalid(format!("fuzz should be in [0, 1], got {}", fuzz)),
+                    Ok,
+                )?,
+            }),
+            MaterialDesc::Dielectric { refraction_index } => Arc::new(Dielectric {
+                refraction_index: positive(refraction_index, "refraction_index")?,
+            }),
+            MaterialDesc::DiffuseLight { emit } => Arc::new(DiffuseLight { emit: hdr_color(emit) }),
+        })
+    }
+}
+
+impl TextureDesc {
+    fn build(self, dir: &Path) -> Result<TextureArc, DescError> {
+        Ok(match self {
+            TextureDesc::Color(c) => Arc::new(color(c)),
+            TextureDesc::Typed(TypedTextureDesc::Solid { color }) => Arc::new(hdr_color(color)),
+            TextureDesc::Typed(TypedTextureDesc::Checker { even, odd, scale }) => Arc::new(Checker {
+                even: even.build(dir)?,
+                odd: odd.build(dir)?,
+                scale: positive(scale, "scale")?,
+            }),
+            TextureDesc::Typed(TypedTextureDesc::Image { path }) => {
+                let path = dir.join(path);
+                Arc::new(ImageTexture::load(&path).map_err(|e| DescError::Image(path, e))?)
+            }
+            TextureDesc::Typed(TypedTextureDesc::Noise { pattern, seed, scale, octaves, low, high }) => {
+                let pattern = match pattern {
+                    NoisePatternDesc::Fbm => NoisePattern::Fbm,
+                    NoisePatternDesc::Turbulence => NoisePattern::Turbulence,
+                    NoisePatternDesc::Marble => NoisePattern::Marble,
+                    NoisePatternDesc::Wood => NoisePattern::Wood,
+                };
+                let mut noise = Noise::new(pattern, seed)
+                    .colors(hdr_color(low.unwrap_or([0.; 3])), hdr_color(high.unwrap_or([1.; 3])));
+                if let Some(scale) = scale {
+                    noise = noise.scale(positive(scale, "scale")?);
+                }
+                if let Some(octaves) = octaves {
+                    noise = noise.octaves(octaves);
+                }
+                Arc::new(noise)
+            }
+        })
+    }
+}
+
+impl MaterialRef {
+    fn build(self, materials: &HashMap<String, MaterialArc>, dir: &Path) -> Result<MaterialArc, DescError> {
+        match self {
+            MaterialRef::Named(name) => materials
+                .get(&name)
+                .cloned()
+                .map_or_else(|| invalid(format!("unknown material '{}'", name)), Ok),
+            MaterialRef::Inline(desc) => desc.build(dir),
+        }
+    }
+}
+
+impl ObjectDesc {
+    fn add(
+        self,
+        scene: SceneBuilder,
+        materials: &HashMap<String, MaterialArc>,
+        dir: &Path,
+    ) -> Result<SceneBuilder, DescError> {
+        Ok(match self {
+            ObjectDesc::Sphere { center, radius, material } => scene.add_sphere(
+                Sphere::new()
+                    .center(vector(center))
+                    .radius(positive(radius, "radius")?)
+                    .material(material.build(materials, dir)?)
+                    .build()
+            ),
+            ObjectDesc::Triangle { vertices: [a, b, c], normals, uvs, material } => {
+                let mut triangle = Triangle::new()
+                    .vertices(vector(a), vector(b), vector(c))
+                    .material(material.build(materials, dir)?);
+                if let Some([a, b, c]) = normals {
+                    triangle = triangle.normals(
+                        direction_vector(a, "normals")?,
+                        direction_vector(b, "normals")?,
+                        direction_vector(c, "normals")?,
+                    );
+                }
+                if let Some([a, b, c]) = uvs {
+                    triangle = triangle.uvs(a, b, c);
+                }
+                scene.add_triangle(triangle.build())
+            }
+            ObjectDesc::Mesh { vertices, indices, normals, uvs, material } => {
+                let n = vertices.len();
+                if indices.iter().flatten().any(|i| *i >= n) {
+                    return invalid("vertex index out of bounds".to_string());
+                }
+                if normals.as_ref().is_some_and(|ns| ns.len() != n) {
+                    return invalid("normal per vertex expected".to_string());
+                }
+                if uvs.as_ref().is_some_and(|uvs| uvs.len() != n) {
+                    return invalid("uv per vertex expected".to_string());
+                }
+                let mut mesh = TriangleMesh::new()
+                    .vertices(vertices.into_iter().map(vector).collect())
+                    .indices(indices)
+                    .material(material.build(materials, dir)?);
+                if let Some(normals) = normals {
+                    mesh = mesh.normals(normals
+                        .into_iter()
+                        .map(|n| direction_vector(n, "normals"))
+                        .collect::<Result<_, _>>()?);
+                }
+                if let Some(uvs) = uvs {
+                    mesh = mesh.uvs(uvs);
+                }
+                scene.add_mesh(mesh.build())
+            }
+            ObjectDesc::Obj { path } => load_obj(&dir.join(path))
+                .map_err(DescError::Obj)?
+                .into_iter()
+                .fold(scene, |scene, mesh| scene.add_mesh(mesh)),
+        })
+    }
+}
+
+impl LightDesc {
+    fn add(self, scene: SceneBuilder) -> Result<SceneBuilder, DescError> {
+        Ok(match self {
+            LightDesc::Point { position, intensity } => scene.add_point_light(PointLight {
+                position: vector(position),
+                intensity: hdr_color(intensity),
+            }),
+            LightDesc::Spot { position, direction, intensity, cone_angle, falloff_angle } => {
+                if !(0. ..=180.).contains(&cone_angle) {
+                    return invalid(format!("cone_angle should be in [0, 180], got {}", cone_angle));
+                }
+                let falloff_angle = falloff_angle.unwrap_or(cone_angle);
+                if !(0. ..=cone_angle).contains(&falloff_angle) {
+                    return invalid(format!(
+                        "falloff_angle should be in [0, cone_angle], got {}", falloff_angle,
+                    ));
+                }
+                scene.add_spot_light(SpotLight {
+                    position: vector(position),
+                    direction: direction_vector(direction, "direction")?,
+                    intensity: hdr_color(intensity),
+                    cone_angle,
+                    falloff_angle,
+                })
+            }
+            LightDesc::Directional { direction, irradiance } => {
+                scene.add_directional_light(DirectionalLight {
+                    direction: direction_vector(direction, "direction")?,
+                    irradiance: hdr_color(irradiance),
+                })
+            }
+        })
+    }
+}
+
+impl BackgroundDesc {
+    fn build(self, dir: &Path) -> Result<Background, DescError> {
+        Ok(match self {
+            BackgroundDesc::Solid { color: c } => {
+                let c = radiance(c);
+                Box::new(move |_| c)
+            }
+            BackgroundDesc::Gradient { bottom, top } => {
+                let bottom = radiance(bottom);
+                let top = radiance(top);
+                Box::new(move |Ray { dir, .. }| {
+                    let t = 0.5 * (dir.y + 1.);
+                    let mut c = (1. - t) * bottom;
+                    c += t * top;
+                    c
+                })
+            }
+            BackgroundDesc::Axes { color: c, falloff: [fr, fg, fb] } => {
+                let c = radiance(c);
+                Box::new(move |Ray { dir, .. }| {
+                    let t = 0.5 * (dir.get() + Vector::new(1., 1., 1.));
+                    Color {
+                        r: c.r * (1. - t[0] * fr),
+                        g: c.g * (1. - t[1] * fg),
+                        b: c.b * (1. - t[2] * fb),
+                    }
+                })
+            }
+            BackgroundDesc::Image { path, intensity } => {
+                let path = dir.join(path);
+                let intensity = intensity.unwrap_or(1.);
+                let map = Image::read_linear(&path).map_err(|e| DescError::Image(path, e))?;
+                Box::new(move |Ray { dir, .. }| {
+                    let u = 0.5 + dir.x.atan2(-dir.z) / (2. * std::f64::consts::PI);
+                    let v = dir.y.clamp(-1., 1.).acos() / std::f64::consts::PI;
+                    let col = ((u * map.w() as f64) as usize).min(map.w() - 1);
+                    let row = ((v * map.h() as f64) as usize).min(map.h() - 1);
+                    intensity * Color::<f64>::from(map[(row, col)])
+                })
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HEADER: &str = "
+        width = 4
+        height = 3
+        [camera]
+        pos = [0, 0, 0]
+        up = [0, 1, 0]
+        to = [0, 0, -1]
+        vfov = 60
+        [background]
+        type = \"solid\"
+        color = [255, 255, 255]
+    ";
+
+    fn error(body: &str) -> String {
+        match parse(&format!("{}{}", HEADER, body), Path::new("scene.toml")) {
+            Ok(_) => panic!("scene should be invalid"),
+            Err(e) => e.to_string(),
+        }
+    }
+
+    #[test]
+    fn shipped_scene() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("../scenes/spheres.toml");
+        let scene = load_scene(&path).unwrap().build();
+        assert_eq!((scene.width.get(), scene.height.get()), (600, 338));
+    }
+
+    #[test]
+    fn minimal_scene() {
+        assert!(parse(HEADER, Path::new("scene.toml")).is_ok());
+    }
+
+    #[test]
+    fn context_of_errors() {
+        let spheres = "
+            [[objects]]
+            type = \"sphere\"
+            center = [0, 0, -1]
+            radius = 1
+            material = { type = \"dielectric\", refraction_index = 1.5 }
+        ";
+        let body = format!("{}{}{}", spheres, spheres, spheres.replace("radius = 1", "radius = -1"));
+        assert_eq!(error(&body), "scene.toml: object #3: radius should be positive, got -1");
+        let body = "
+            [materials.rough]
+            type = \"metal\"
+            albedo = [255, 255, 255]
+            fuzz = 2
+        ";
+        assert_eq!(error(body), "scene.toml: material 'rough': fuzz should be in [0, 1], got 2");
+        let body = "
+            [[objects]]
+            type = \"sphere\"
+            center = [0, 0, -1]
+            radius = 1
+            material = \"missing\"
+        ";
+        assert_eq!(error(body), "scene.toml: object #1: unknown material 'missing'");
+    }
+
+    #[test]
+    fn unknown_field() {
+        let e = parse(&format!("{}\nsamples = 4\n", HEADER), Path::new("scene.toml")).err().unwrap();
+        assert!(matches!(e, SceneFileError::Syntax(..)));
+    }
+
+    #[test]
+    fn lights() {
+        let lights = "
+            [[lights]]
+            type = \"point\"
+            position = [0, 2, 0]
+            intensity = [10, 10, 10]
+            [[lights]]
+            type = \"spot\"
+            position = [0, 2, 0]
+            direction = [0, -1, 0]
+            intensity = [10, 10, 10]
+            cone_angle = 30
+            [[lights]]
+            type = \"directional\"
+            direction = [1, -1, 0]
+            irradiance = [2, 2, 2]
+        ";
+        let scene = parse(&format!("{}{}", HEADER, lights), Path::new("scene.toml")).unwrap().build();
+        assert_eq!(scene.delta_lights.len(), 3);
+        let body = lights.replace("cone_angle = 30", "cone_angle = 30\nfalloff_angle = 40");
+        assert_eq!(error(&body), "scene.toml: light #2: falloff_angle should be in [0, cone_angle], got 40");
+        let body = lights.replace("[1, -1, 0]", "[0, 0, 0]");
+        assert_eq!(error(&body), "scene.toml: light #3: direction should be non-zero");
+    }
+
+    #[test]
+    fn zero_directions() {
+        let triangle = "
+            [[objects]]
+            type = \"triangle\"
+            vertices = [[0, 0, -1], [1, 0, -1], [0, 1, -1]]
+            normals = [[0, 0, 1], [0, 0, 0], [0, 0, 1]]
+            material = { type = \"lambertian\", albedo = [255, 255, 255] }
+        ";
+        assert_eq!(error(triangle), "scene.toml: object #1: normals should be non-zero");
+        let mesh = "
+            [[objects]]
+            type = \"mesh\"
+            vertices = [[0, 0, -1], [1, 0, -1], [0, 1, -1]]
+            indices = [[0, 1, 2]]
+            normals = [[0, 0, 1], [0, 0, 1], [0, 0, 0]]
+            material = { type = \"lambertian\", albedo = [255, 255, 255] }
+        ";
+        assert_eq!(error(mesh), "scene.toml: object #1: normals should be non-zero");
+        let header = HEADER.replace("up = [0, 1, 0]", "up = [0, 0, 0]");
+        let e = parse(&header, Path::new("scene.toml")).err().unwrap();
+        assert_eq!(e.to_string(), "scene.toml: up should be non-zero");
+    }
+}
@@ -0,0 +1,56 @@
+use std::sync::Arc;
+
+use color::Color;
+
+use crate::textures::{Texture, TextureArc};
+use crate::utils::Positive;
+use crate::Vector;
+
+/// Solid 3D checker of cubes with side `scale` filled by alternating textures.
+pub struct Checker {
+    pub even: TextureArc,
+    pub odd: TextureArc,
+    pub scale: Positive<f64>,
+}
+
+impl Checker {
+    pub fn new(
+        even: impl Texture + Send + Sync + 'static,
+        odd: impl Texture + Send + Sync + 'static,
+        scale: Positive<f64>,
+    ) -> Self {
+        Checker { even: Arc::new(even), odd: Arc::new(odd), scale }
+    }
+}
+
+impl Texture for Checker {
+    fn value(&self, uv: (f64, f64), p: &Vector) -> Color<f64> {
+        let cell = p.map(|x| (x / self.scale.get()).floor() as i64);
+        if (cell.x + cell.y + cell.z).rem_euclid(2) == 0 {
+            self.even.value(uv, p)
+        } else {
+            self.odd.value(uv, p)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alternates() {
+        let black = Color { r: 0., g: 0., b: 0. };
+        let white = Color { r: 1., g: 1., b: 1. };
+        let checker = Checker::new(white, black, Positive::new(0.5).unwrap());
+        let at = |x, y, z| checker.value((0., 0.), &Vector::new(x, y, z)).r;
+        assert_eq!(at(0.1, 0.1, 0.1), 1.);
+        assert_eq!(at(0.6, 0.1, 0.1), 0.);
+        assert_eq!(at(0.6, 0.6, 0.1), 1.);
+        assert_eq!(at(0.6, 0.6, 0.6), 0.);
+        // Cells keep alternating across zero.
+        assert_eq!(at(-0.1, 0.1, 0.1), 0.);
+        assert_eq!(at(-0.6, 0.1, 0.1), 1.);
+        assert_eq!(at(-0.6, -0.1, 0.1), 0.);
+    }
+}
@@ -0,0 +1,90 @@
+use std::path::Path;
+
+use color::Color;
+use image::Image;
+
+use crate::textures::Texture;
+use crate::Vector;
+
+/// Image stretched over the `[0, 1]` uv square and repeated outside of it,
+/// `v` goes from the bottom row up.
+pub struct ImageTexture {
+    image: Image<f32>,
+}
+
+impl ImageTexture {
+    /// Texture of linear radiance.
+    pub fn new(image: Image<f32>) -> Self {
+        ImageTexture { image }
+    }
+
+    /// Loads an image file, see `Image::read_linear` for the supported formats.
+    pub fn load(path: &Path) -> Result<Self, image::Error> {
+        Ok(ImageTexture::new(Image::read_linear(path)?))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color<f64> {
+        let w = self.image.w() as i64;
+        let h = self.image.h() as i64;
+        Color::from(self.image[(y.rem_euclid(h) as usize, x.rem_euclid(w) as usize)])
+    }
+}
+
+/// Bilinear filtering between centers of the nearest texels.
+impl Texture for ImageTexture {
+    fn value(&self, (u, v): (f64, f64), _: &Vector) -> Color<f64> {
+        let x = u * self.image.w() as f64 - 0.5;
+        let y = (1. - v) * self.image.h() as f64 - 0.5;
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+        let (x0, y0) = (x0 as i64, y0 as i64);
+        let mut c = (1. - fx) * (1. - fy) * self.texel(x0, y0);
+        c += fx * (1. - fy) * self.texel(x0 + 1, y0);
+        c += (1. - fx) * fy * self.texel(x0, y0 + 1);
+        c += fx * fy * self.texel(x0 + 1, y0 + 1);
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Black and white texels in the top row, red and green ones in the bottom row.
+    fn texture() -> ImageTexture {
+        let c = |r, g, b| color::Color { r, g, b };
+        ImageTexture::new(Image::from(vec![
+            vec![c(0., 0., 0.), c(1., 1., 1.)],
+            vec![c(1., 0., 0.), c(0., 1., 0.)],
+        ]))
+    }
+
+    fn at(u: f64, v: f64) -> (f64, f64, f64) {
+        let c = texture().value((u, v), &Vector::zeros());
+        (c.r, c.g, c.b)
+    }
+
+    #[test]
+    fn texel_centers() {
+        assert_eq!(at(0.25, 0.75), (0., 0., 0.));
+        assert_eq!(at(0.75, 0.75), (1., 1., 1.));
+        assert_eq!(at(0.25, 0.25), (1., 0., 0.));
+        assert_eq!(at(0.75, 0.25), (0., 1., 0.));
+    }
+
+    #[test]
+    fn bilinear() {
+        assert_eq!(at(0.5, 0.75), (0.5, 0.5, 0.5));
+        assert_eq!(at(0.25, 0.5), (0.5, 0., 0.));
+        assert_eq!(at(0.5, 0.5), (0.5, 0.5, 0.25));
+    }
+
+    #[test]
+    fn wraps() {
+        assert_eq!(at(1.25, 0.75), at(0.25, 0.75));
+        assert_eq!(at(-0.75, -0.25), at(0.25, 0.75));
+        // Edges blend with the opposite side of the image.
+        assert_eq!(at(0., 0.75), (0.5, 0.5, 0.5));
+        assert_eq!(at(0.25, 1.), (0.5, 0., 0.));
+    }
+}
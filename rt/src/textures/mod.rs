@@ -0,0 +1,37 @@
+use std::sync::Arc;
+
+use color::Color;
+
+pub use {
+    checker::Checker,
+    image_texture::ImageTexture,
+    noise::{Noise, NoisePattern, Perlin},
+};
+
+use crate::Vector;
+
+mod checker;
+mod image_texture;
+mod noise;
+
+/// Color varying over the surface, solid colors are textures too.
+pub trait Texture {
+    /// Color at the surface coordinates `uv` of the world space point `p`.
+    fn value(&self, uv: (f64, f64), p: &Vector) -> Color<f64>;
+}
+
+pub type TextureArc = Arc<dyn Texture + Send + Sync + 'static>;
+
+/// Linear color.
+impl Texture for Color<f64> {
+    fn value(&self, _: (f64, f64), _: &Vector) -> Color<f64> {
+        *self
+    }
+}
+
+/// 8-bit color scaled to `[0, 1]` without decoding.
+impl Texture for Color<u8> {
+    fn value(&self, _: (f64, f64), _: &Vector) -> Color<f64> {
+        (1. / u8::MAX as f64) * *self
+    }
+}
@@ -0,0 +1,210 @@
+use rand::{Rng, SeedableRng};
+use rand::seq::SliceRandom;
+use rand_chacha::ChaCha8Rng;
+
+use color::Color;
+
+use crate::textures::Texture;
+use crate::utils::Positive;
+use crate::Vector;
+
+const POINT_COUNT: usize = 256;
+
+/// Gradient noise with values in `[-1, 1]`, the same seed gives the same noise.
+pub struct Perlin {
+    gradients: Vec<Vector>,
+    perm: [Vec<usize>; 3],
+}
+
+impl Perlin {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = ChaCha8Rng::seed_from_u64(seed);
+        let gradients = (0..POINT_COUNT)
+            .map(|_| loop {
+                let v = Vector::new(rng.gen(), rng.gen(), rng.gen()) * 2. - Vector::new(1., 1., 1.);
+                let norm = v.norm();
+                if norm > 1e-3 && norm <= 1. {
+                    break v / norm;
+                }
+            })
+            .collect();
+        let mut permutation = || {
+            let mut p: Vec<_> = (0..POINT_COUNT).collect();
+            p.shuffle(&mut rng);
+            p
+        };
+        let perm = [permutation(), permutation(), permutation()];
+        Perlin { gradients, perm }
+    }
+
+    pub fn noise(&self, p: &Vector) -> f64 {
+        let cell = p.map(f64::floor);
+        let f = p - cell;
+        // Hermite smoothing removes grid artifacts of the linear interpolation.
+        let w = f.map(|x| x * x * (3. - 2. * x));
+        let mut sum = 0.;
+        for di in 0..2 {
+            for dj in 0..2 {
+                for dk in 0..2 {
+                    let corner = [cell.x as i64 + di, cell.y as i64 + dj, cell.z as i64 + dk];
+                    let gradient = &self.gradients[self.hash(corner)];
+                    let offset = f - Vector::new(di as f64, dj as f64, dk as f64);
+                    let weight = [(di, w.x), (dj, w.y), (dk, w.z)]
+                        .iter()
+                        .map(|(d, w)| if *d == 1 { *w } else { 1. - w })
+                        .product::<f64>();
+                    sum += weight * gradient.dot(&offset);
+                }
+            }
+        }
+        sum.clamp(-1., 1.)
+    }
+
+    /// Sum of `octaves` noise layers with doubling frequency and halving amplitude.
+    pub fn fbm(&self, p: &Vector, octaves: usize) -> f64 {
+        self.octaves(p, octaves, |x| x)
+    }
+
+    /// Like `fbm` of absolute noise values, always positive.
+    pub fn turbulence(&self, p: &Vector, octaves: usize) -> f64 {
+        self.octaves(p, octaves, f64::abs)
+    }
+
+    fn octaves(&self, p: &Vector, octaves: usize, f: impl Fn(f64) -> f64) -> f64 {
+        let mut sum = 0.;
+        let mut p = *p;
+        let mut weight = 1.;
+        for _ in 0..octaves {
+            sum += weight * f(self.noise(&p));
+            weight *= 0.5;
+            p *= 2.;
+        }
+        sum
+    }
+
+    fn hash(&self, [i, j, k]: [i64; 3]) -> usize {
+        let mask = POINT_COUNT as i64 - 1;
+        self.perm[0][(i & mask) as usize]
+            ^ self.perm[1][(j & mask) as usize]
+            ^ self.perm[2][(k & mask) as usize]
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+pub enum NoisePattern {
+    /// Soft cloud-like blobs.
+    Fbm,
+    /// Billowy pattern with sharp creases.
+    Turbulence,
+    /// Veins along the `z` axis distorted by turbulence.
+    Marble,
+    /// Rings around the `y` axis distorted by noise.
+    Wood,
+}
+
+/// Blend of two colors driven by the noise pattern at the world space point.
+pub struct Noise {
+    perlin: Perlin,
+    pattern: NoisePattern,
+    scale: Positive<f64>,
+    octaves: usize,
+    low: Color<f64>,
+    high: Color<f64>,
+}
+
+impl Noise {
+    /// Black and white pattern of 7 octaves with unit scale.
+    pub fn new(pattern: NoisePattern, seed: u64) -> Self {
+        Noise {
+            perlin: Perlin::new(seed),
+            pattern,
+            scale: Positive::new(1.).unwrap(),
+            octaves: 7,
+            low: Color { r: 0., g: 0., b: 0. },
+            high: Color { r: 1., g: 1., b: 1. },
+        }
+    }
+
+    /// Frequency of the pattern, features get smaller as it grows.
+    pub fn scale(mut self, scale: Positive<f64>) -> Self {
+        self.scale = scale;
+        self
+    }
+
+    pub fn octaves(mut self, octaves: usize) -> Self {
+        self.octaves = octaves;
+        self
+    }
+
+    pub fn colors(mut self, low: Color<f64>, high: Color<f64>) -> Self {
+        self.low = low;
+        self.high = high;
+        self
+    }
+
+    /// Pattern value in `[0, 1]`.
+    fn pattern_value(&self, p: &Vector) -> f64 {
+        let p = self.scale.get() * p;
+        let t = match self.pattern {
+            NoisePattern::Fbm => 0.5 * (1. + self.perlin.fbm(&p, self.octaves)),
+            NoisePattern::Turbulence => self.perlin.turbulence(&p, self.octaves),
+            NoisePattern::Marble => {
+                0.5 * (1. + (p.z + 10. * self.perlin.turbulence(&p, self.octaves)).sin())
+            }
+            NoisePattern::Wood => {
+                let rings = 4. * p.x.hypot(p.z) + 0.5 * self.perlin.fbm(&p, self.octaves);
+                rings - rings.floor()
+            }
+        };
+        t.clamp(0., 1.)
+    }
+}
+
+impl Texture for Noise {
+    fn value(&self, _: (f64, f64), p: &Vector) -> Color<f64> {
+        let t = self.pattern_value(p);
+        let mut c = (1. - t) * self.low;
+        c += t * self.high;
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn points() -> impl Iterator<Item = Vector> {
+        (0..2000).map(|i| {
+            let i = i as f64;
+            Vector::new((i * 0.731).sin() * 7., (i * 0.377).cos() * 5., i * 0.0113 - 11.)
+        })
+    }
+
+    #[test]
+    fn seeded() {
+        let (a, b, c) = (Perlin::new(3), Perlin::new(3), Perlin::new(4));
+        assert!(points().all(|p| a.noise(&p).to_bits() == b.noise(&p).to_bits()));
+        assert!(points().any(|p| a.noise(&p) != c.noise(&p)));
+    }
+
+    #[test]
+    fn ranges() {
+        let perlin = Perlin::new(0);
+        for p in points() {
+            assert!((-1. ..=1.).contains(&perlin.noise(&p)));
+            // Weights of the octaves sum up to less than 2.
+            assert!((-2. ..2.).contains(&perlin.fbm(&p, 7)));
+            assert!((0. ..2.).contains(&perlin.turbulence(&p, 7)));
+        }
+        let patterns = [NoisePattern::Fbm, NoisePattern::Turbulence, NoisePattern::Marble, NoisePattern::Wood];
+        for pattern in patterns {
+            let noise = Noise::new(pattern, 0);
+            let values: Vec<_> = points().map(|p| noise.pattern_value(&p)).collect();
+            let min = values.iter().copied().fold(f64::INFINITY, f64::min);
+            let max = values.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+            assert!(0. <= min && max <= 1., "{:?}", pattern);
+            // The pattern is neither flat nor saturated.
+            assert!(max - min > 0.5, "{:?} spans [{}, {}]", pattern, min, max);
+        }
+    }
+}
@@ -0,0 +1,122 @@
+use std::f64::consts::PI;
+
+/// Rectangle of pixels rendered by a single thread at once.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Tile {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+}
+
+/// Order in which threads pick up tiles.
+#[derive(Clone, Copy, Debug, Default)]
+pub enum TileOrder {
+    /// Rows of tiles from the top.
+    Scanline,
+    /// Rings of tiles from the center of the image outwards, so the subject shows up first.
+    #[default]
+    Spiral,
+    /// Hilbert curve, consecutive tiles are always neighbours.
+    Hilbert,
+}
+
+/// Tiles covering the image, the ones at the right and bottom edges may be smaller.
+pub(crate) fn tiles(width: usize, height: usize, size: usize, order: TileOrder) -> Vec<Tile> {
+    let size = size.max(1);
+    let cols = width.div_ceil(size);
+    let rows = height.div_ceil(size);
+    let mut cells: Vec<_> = (0..rows).flat_map(|ty| (0..cols).map(move |tx| (tx, ty))).collect();
+    match order {
+        TileOrder::Scanline => {}
+        TileOrder::Spiral => {
+            let cx = (cols as f64 - 1.) / 2.;
+            let cy = (rows as f64 - 1.) / 2.;
+            let key = |&(tx, ty): &(usize, usize)| {
+                let dx = tx as f64 - cx;
+                let dy = ty as f64 - cy;
+                let ring = dx.abs().max(dy.abs()).round();
+                // Clockwise from the top.
+                let angle = dx.atan2(-dy).rem_euclid(2. * PI);
+                (ring, angle)
+            };
+            cells.sort_by(|a, b| key(a).partial_cmp(&key(b)).unwrap());
+        }
+        TileOrder::Hilbert => {
+            let n = cols.max(rows).next_power_of_two();
+            cells.sort_by_key(|&(tx, ty)| hilbert_index(n, tx, ty));
+        }
+    }
+    cells
+        .into_iter()
+        .map(|(tx, ty)| Tile {
+            x: tx * size,
+            y: ty * size,
+            width: size.min(width - tx * size),
+            height: size.min(height - ty * size),
+        })
+        .collect()
+}
+
+/// Distance along the Hilbert curve filling the `n` by `n` grid, `n` is a power of two.
+fn hilbert_index(n: usize, mut x: usize, mut y: usize) -> usize {
+    let mut d = 0;
+    let mut s = n / 2;
+    while s > 0 {
+        let rx = usize::from(x & s > 0);
+        let ry = usize::from(y & s > 0);
+        d += s * s * ((3 * rx) ^ ry);
+        // Rotates the quadrant so the curve inside of it starts at the right corner.
+        if ry == 0 {
+            if rx == 1 {
+                x = s - 1 - (x & (s - 1));
+                y = s - 1 - (y & (s - 1));
+            }
+            std::mem::swap(&mut x, &mut y);
+        }
+        s /= 2;
+    }
+    d
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn covers_once(tiles: &[Tile], width: usize, height: usize) -> bool {
+        let mut covered = vec![0; width * height];
+        for t in tiles {
+            for y in t.y..t.y + t.height {
+                for x in t.x..t.x + t.width {
+                    covered[y * width + x] += 1;
+                }
+            }
+        }
+        covered.iter().all(|c| *c == 1)
+    }
+
+    #[test]
+    fn cover_image() {
+        for order in [TileOrder::Scanline, TileOrder::Spiral, TileOrder::Hilbert] {
+            for (width, height, size) in [(100, 60, 16), (7, 5, 8), (33, 1, 4)] {
+                assert!(covers_once(&tiles(width, height, size, order), width, height));
+            }
+        }
+    }
+
+    #[test]
+    fn hilbert_neighbours() {
+        let tiles = tiles(64, 64, 8, TileOrder::Hilbert);
+        for pair in tiles.windows(2) {
+            let dx = (pair[0].x as i64 - pair[1].x as i64).abs();
+            let dy = (pair[0].y as i64 - pair[1].y as i64).abs();
+            assert_eq!(dx + dy, 8);
+        }
+    }
+
+    #[test]
+    fn spiral_starts_at_center() {
+        let tiles = tiles(48, 48, 16, TileOrder::Spiral);
+        assert_eq!(tiles[0], Tile { x: 16, y: 16, width: 16, height: 16 });
+    }
+}
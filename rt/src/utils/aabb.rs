@@ -0,0 +1,66 @@
+use crate::Vector;
+
+/// Axis-aligned bounding box.
+#[derive(Clone, Debug)]
+pub(crate) struct Aabb {
+    pub(crate) min: Vector,
+    pub(crate) max: Vector,
+}
+
+impl Aabb {
+    pub(crate) fn new(min: Vector, max: Vector) -> Self {
+        Aabb { min, max }
+    }
+
+    /// Box that contains nothing, neutral element for `union`.
+    pub(crate) fn empty() -> Self {
+        Aabb {
+            min: Vector::repeat(f64::INFINITY),
+            max: Vector::repeat(f64::NEG_INFINITY),
+        }
+    }
+
+    pub(crate) fn union(&self, other: &Aabb) -> Aabb {
+        Aabb {
+            min: self.min.zip_map(&other.min, f64::min),
+            max: self.max.zip_map(&other.max, f64::max),
+        }
+    }
+
+    pub(crate) fn grow(&self, p: &Vector) -> Aabb {
+        Aabb {
+            min: self.min.zip_map(p, f64::min),
+            max: self.max.zip_map(p, f64::max),
+        }
+    }
+
+    pub(crate) fn centroid(&self) -> Vector {
+        0.5 * (self.min + self.max)
+    }
+
+    pub(crate) fn surface_area(&self) -> f64 {
+        let d = self.max - self.min;
+        if d.iter().any(|x| *x < 0.) {
+            return 0.;
+        }
+        2. * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Slab test of the ray `orig + t * dir` against the box,
+    /// `inv_dir` is a component-wise inverse of the ray direction.
+    pub(crate) fn touch(&self, orig: &Vector, inv_dir: &Vector, t_min: f64, t_max: f64) -> bool {
+        let mut t_min = t_min;
+        let mut t_max = t_max;
+        for axis in 0..3 {
+            let t0 = (self.min[axis] - orig[axis]) * inv_dir[axis];
+            let t1 = (self.max[axis] - orig[axis]) * inv_dir[axis];
+            let (t0, t1) = if inv_dir[axis] < 0. { (t1, t0) } else { (t0, t1) };
+            t_min = t0.max(t_min);
+            t_max = t1.min(t_max);
+            if t_max < t_min {
+                return false;
+            }
+        }
+        true
+    }
+}
@@ -4,9 +4,11 @@ pub use {
     uni_float::UniFloat,
 };
 pub(crate) use {
+    aabb::Aabb,
     vector::*,
 };
 
+mod aabb;
 mod norm_vector;
 mod positive;
 mod vector;
@@ -1,11 +1,13 @@
 use std::f64::consts::PI;
 
+use crate::sampler::Samples;
 use crate::utils::NormVector;
 use crate::Vector;
 
-pub(crate) fn random_unit() -> Vector {
-    let a = 2. * PI * rand::random::<f64>();
-    let z = -1. + 2. * rand::random::<f64>();
+pub(crate) fn random_unit(samples: &mut Samples) -> Vector {
+    let (u, v) = samples.get_2d();
+    let a = 2. * PI * u;
+    let z = -1. + 2. * v;
     let r = (1. - z * z).sqrt();
     Vector::new(
         r * a.cos(),
@@ -18,6 +20,27 @@ pub(crate) fn reflect(v: &NormVector, n: &NormVector) -> NormVector {
     NormVector::from(v.get() - 2. * v.dot(n) * n.get())
 }
 
+/// Refracts `v` on the surface with normal `n` facing against `v`,
+/// `ratio` is a ratio of the refraction indices of the media.
+/// Callers check for total internal reflection, where there is no refracted ray.
+pub(crate) fn refract(v: &NormVector, n: &NormVector, ratio: f64) -> NormVector {
+    let cos = (-v.dot(n)).min(1.);
+    let perp = ratio * (v.get() + cos * n.get());
+    let cos2 = 1. - perp.norm_squared();
+    debug_assert!(cos2 > -1e-9, "total internal reflection");
+    // Rounding may leave it slightly negative right at the critical angle.
+    let parallel = -cos2.max(0.).sqrt() * n.get();
+    NormVector::from(perp + parallel)
+}
+
 pub(crate) fn clone_vec(v: &Vector) -> Vector {
     Vector::from_data(v.data)
 }
+
+/// Two unit vectors completing `n` to a right-handed orthonormal basis.
+pub(crate) fn orthonormal_basis(n: &NormVector) -> (Vector, Vector) {
+    let helper = if n.x.abs() > 0.9 { Vector::new(0., 1., 0.) } else { Vector::new(1., 0., 0.) };
+    let s = n.cross(&helper).normalize();
+    let t = n.cross(&s);
+    (s, t)
+}
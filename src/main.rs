@@ -1,150 +1,505 @@
 extern crate image;
+extern crate rayon;
 extern crate rt;
+extern crate structopt;
 
-use std::{env, io, process};
+use std::{io, process};
 use std::io::Write;
 use std::num::NonZeroUsize;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
 use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Mutex;
+use std::time::Instant;
 
-use image::Color;
-use rt::{Camera, Lambertian, Logger, Metal, Positive, Ray, Scene, Sphere, UniFloat};
-use rt::NormVector;
-use rt::Vector;
+use structopt::clap;
+use structopt::StructOpt;
 
+use image::{ExrCompression, ExrPixelType, ExrWriter, Image, Sample};
+use rt::{Adaptive, Aovs, Encoding, Logger, Sampled, Sampler, SceneFileError, TileOrder, ToneMap};
+
+/// Renders a scene described in TOML file.
+#[derive(StructOpt)]
+#[structopt(name = "rust-rt", after_help = EXIT_CODES)]
 struct Cli {
+    /// Scene description file
+    #[structopt(parse(from_os_str))]
+    scene_path: PathBuf,
+
+    /// Where to save the rendered image
+    #[structopt(parse(from_os_str))]
     save_path: PathBuf,
+
+    /// Image format, guessed by the save path extension by default [possible values: png, ppm, pam, exr, hdr, pfm]
+    #[structopt(short, long)]
+    format: Option<Format>,
+
+    /// Image width, height follows the camera aspect ratio if not set
+    #[structopt(short = "W", long)]
+    width: Option<NonZeroUsize>,
+
+    /// Image height, width follows the camera aspect ratio if not set
+    #[structopt(short = "H", long)]
+    height: Option<NonZeroUsize>,
+
+    /// Number of rays traced through each pixel, the average one with adaptive sampling
+    #[structopt(short, long, default_value = "1000")]
+    samples: NonZeroUsize,
+
+    /// Stop sampling pixels once the relative error of their luminance gets below the threshold
+    #[structopt(long, parse(try_from_str = parse_threshold))]
+    adaptive: Option<f64>,
+
+    /// Number of rays traced through each pixel before adaptive sampling checks the error
+    #[structopt(long, default_value = "16")]
+    min_samples: NonZeroUsize,
+
+    /// Render in passes of the number of rays per pixel, saving the image after each of them
+    #[structopt(long)]
+    progressive: Option<NonZeroUsize>,
+
+    /// PNG image of the number of rays traced through each pixel, from blue for the least to red
+    #[structopt(long, parse(from_os_str))]
+    sample_heatmap: Option<PathBuf>,
+
+    /// Maximum number of ray bounces, Russian roulette usually ends paths much earlier
+    #[structopt(short, long, default_value = "32")]
+    depth: usize,
+
+    /// Number of bounces before paths may be terminated by Russian roulette, 0 turns it off
+    #[structopt(long, default_value = "3")]
+    roulette_depth: usize,
+
+    /// Sampler of pixel positions and bounces: random, stratified, halton or sobol
+    #[structopt(long, default_value = "sobol", parse(try_from_str = parse_sampler))]
+    sampler: Sampler,
+
+    /// Seed of the random sampling, the same seed renders the same image
+    #[structopt(long, default_value = "0")]
+    seed: u64,
+
+    /// Output gamma, either `srgb` for the sRGB curve or a number, 1 keeps radiance linear
+    #[structopt(short, long, default_value = "srgb", parse(try_from_str = parse_gamma))]
+    gamma: Encoding,
+
+    /// Tone mapping of PNG and Netpbm images, `reinhard:<white>` maps luminance `white` to 1
+    /// [possible values: clamp, reinhard, reinhard:<white>, aces, hable]
+    #[structopt(long, default_value = "clamp", parse(try_from_str = parse_tone_map))]
+    tone_map: ToneMap,
+
+    /// Exposure compensation in stops applied before tone mapping
+    #[structopt(short, long, default_value = "0", allow_hyphen_values = true)]
+    exposure: f64,
+
+    /// Bits per channel of PNG and Netpbm images [possible values: 8, 16]
+    #[structopt(short, long, default_value = "8", parse(try_from_str = parse_bit_depth))]
+    bit_depth: u8,
+
+    /// Make background transparent, supported by PNG, PAM and EXR images
+    #[structopt(short, long)]
+    alpha: bool,
+
+    /// Compression of EXR images [possible values: none, rle, zips, zip]
+    #[structopt(long, default_value = "zip", parse(try_from_str = parse_exr_compression))]
+    exr_compression: ExrCompression,
+
+    /// Store 32-bit floats in EXR images instead of halves
+    #[structopt(long)]
+    exr_float: bool,
+
+    /// Number of rendering threads, number of CPUs by default
+    #[structopt(short, long)]
+    threads: Option<NonZeroUsize>,
+
+    /// Width and height of square tiles rendered by threads
+    #[structopt(long, default_value = "16")]
+    tile_size: NonZeroUsize,
+
+    /// Order of rendering tiles: scanline, spiral from the center or hilbert
+    #[structopt(long, default_value = "spiral", parse(try_from_str = parse_tile_order))]
+    tile_order: TileOrder,
+
+    /// Do not report progress
+    #[structopt(short, long, conflicts_with = "verbose")]
+    quiet: bool,
+
+    /// Report progress in percents and rendering settings
+    #[structopt(short, long)]
+    verbose: bool,
+}
+
+const EXIT_CODES: &str = "EXIT CODES:
+    0     Rendered image is saved, or help is shown
+    64    Invalid options, unknown image format or transparency not supported by the format
+    65    Invalid scene file or model
+    66    Scene file or environment map can not be read
+    70    Rendered image can not be encoded
+    71    Rendering threads can not be started
+    74    Rendered image can not be written";
+
+fn parse_gamma(s: &str) -> Result<Encoding, String> {
+    if s.eq_ignore_ascii_case("srgb") {
+        return Ok(Encoding::Srgb);
+    }
+    match s.parse::<f64>() {
+        Ok(1.) => Ok(Encoding::Linear),
+        Ok(gamma) if gamma > 0. => Ok(Encoding::Gamma(gamma)),
+        _ => Err(format!("Gamma should be 'srgb' or a positive number, got '{}'", s)),
+    }
+}
+
+fn parse_threshold(s: &str) -> Result<f64, String> {
+    match s.parse::<f64>() {
+        Ok(threshold) if threshold >= 0. && threshold.is_finite() => Ok(threshold),
+        _ => Err(format!("Threshold should be a non-negative number, got '{}'", s)),
+    }
+}
+
+fn parse_sampler(s: &str) -> Result<Sampler, String> {
+    match s.to_lowercase().as_str() {
+        "random" => Ok(Sampler::Random),
+        "stratified" => Ok(Sampler::Stratified),
+        "halton" => Ok(Sampler::Halton),
+        "sobol" => Ok(Sampler::Sobol),
+        _ => Err(format!("Unknown sampler '{}'", s)),
+    }
+}
+
+fn parse_tile_order(s: &str) -> Result<TileOrder, String> {
+    match s.to_lowercase().as_str() {
+        "scanline" => Ok(TileOrder::Scanline),
+        "spiral" => Ok(TileOrder::Spiral),
+        "hilbert" => Ok(TileOrder::Hilbert),
+        _ => Err(format!("Unknown tile order '{}'", s)),
+    }
+}
+
+fn parse_tone_map(s: &str) -> Result<ToneMap, String> {
+    let s = s.to_lowercase();
+    if let Some(white) = s.strip_prefix("reinhard:") {
+        return match white.parse::<f64>() {
+            Ok(white) if white > 0. => Ok(ToneMap::ExtendedReinhard { white }),
+            _ => Err(format!("White point should be a positive number, got '{}'", white)),
+        };
+    }
+    match s.as_str() {
+        "clamp" => Ok(ToneMap::Clamp),
+        "reinhard" => Ok(ToneMap::Reinhard),
+        "aces" => Ok(ToneMap::Aces),
+        "hable" => Ok(ToneMap::Hable),
+        _ => Err(format!("Unknown tone mapping '{}'", s)),
+    }
+}
+
+fn parse_bit_depth(s: &str) -> Result<u8, String> {
+    match s {
+        "8" => Ok(8),
+        "16" => Ok(16),
+        _ => Err(format!("Bit depth should be 8 or 16, got '{}'", s)),
+    }
+}
+
+fn parse_exr_compression(s: &str) -> Result<ExrCompression, String> {
+    match s.to_lowercase().as_str() {
+        "none" => Ok(ExrCompression::None),
+        "rle" => Ok(ExrCompression::Rle),
+        "zips" => Ok(ExrCompression::Zips),
+        "zip" => Ok(ExrCompression::Zip),
+        _ => Err(format!("Unknown EXR compression '{}'", s)),
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
+enum Format {
+    Png,
+    /// Binary Netpbm RGB.
+    Ppm,
+    /// Netpbm arbitrary map, RGB with optional alpha.
+    Pam,
+    /// Beauty in the default layer along with albedo, normal and depth layers.
+    Exr,
+    /// Radiance RGBE.
+    Hdr,
+    /// Portable float map.
+    Pfm,
 }
 
-impl Cli {
-    fn new(args: &[String]) -> Option<Cli> {
-        match args {
-            [_, sp] => Some(Cli {
-                save_path: PathBuf::from(sp),
-            }),
-            _ => None
+impl Format {
+    fn from_path(path: &Path) -> Option<Format> {
+        path.extension()?.to_str()?.parse().ok()
+    }
+
+    fn has_alpha(self) -> bool {
+        match self {
+            Format::Png | Format::Pam | Format::Exr => true,
+            Format::Ppm | Format::Hdr | Format::Pfm => false,
+        }
+    }
+}
+
+impl FromStr for Format {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "png" => Ok(Format::Png),
+            "ppm" => Ok(Format::Ppm),
+            "pam" => Ok(Format::Pam),
+            "exr" => Ok(Format::Exr),
+            "hdr" => Ok(Format::Hdr),
+            "pfm" => Ok(Format::Pfm),
+            _ => Err(format!("Unknown image format '{}'", s)),
         }
     }
 }
 
 enum Error {
-    Cli,
+    Cli(clap::Error),
+    UnknownFormat(PathBuf),
+    NoAlpha(Format),
+    ThreadPool(rayon::ThreadPoolBuildError),
+    Scene(SceneFileError),
     ImgWriteIO(io::Error),
+    ImgWrite(image::Error),
+}
+
+impl From<SceneFileError> for Error {
+    fn from(e: SceneFileError) -> Error {
+        Error::Scene(e)
+    }
 }
 
 impl From<image::Error> for Error {
     fn from(e: image::Error) -> Error {
         match e {
-            image::Error::WriteIO(e) => Error::ImgWriteIO(e)
+            image::Error::WriteIO(e) => Error::ImgWriteIO(e),
+            e => Error::ImgWrite(e),
         }
     }
 }
 
 fn main() {
-    let args: Vec<_> = env::args().collect();
-    let progress = AtomicUsize::new(0);
-    let logger = Box::new(move |_, _| {
-        if progress.fetch_add(1, Ordering::SeqCst) % 10 == 0 {
-            print!(".");
-            io::stdout().flush().unwrap();
-        }
-    });
-    match try_main(args, logger) {
-        Ok(()) => println!("Finished!"),
+    match try_main() {
+        Ok(()) => {}
         Err(e) => match e {
-            Error::Cli => {
-                eprintln!("Only one arg should be passed: render save path");
+            Error::Cli(e) => match e.kind {
+                clap::ErrorKind::HelpDisplayed | clap::ErrorKind::VersionDisplayed => {
+                    println!("{}", e.message);
+                    process::exit(exitcode::OK)
+                }
+                _ => {
+                    eprintln!("{}", e.message);
+                    process::exit(exitcode::USAGE)
+                }
+            },
+            Error::UnknownFormat(path) => {
+                eprintln!("Unable to guess image format of {}, use --format", path.display());
+                process::exit(exitcode::USAGE)
+            }
+            Error::NoAlpha(format) => {
+                eprintln!("{:?} images do not support transparency", format);
+                process::exit(exitcode::USAGE)
+            }
+            Error::ThreadPool(e) => {
+                eprintln!("Unable to start rendering threads: {}", e);
+                process::exit(exitcode::OSERR)
+            }
+            Error::Scene(SceneFileError::IO(path, e)) => {
+                eprintln!("Error while reading scene file {}: {}", path.display(), e);
                 process::exit(exitcode::NOINPUT)
             }
+            Error::Scene(SceneFileError::Image(path, image::Error::ReadIO(e))) => {
+                eprintln!("Error while reading environment map {}: {}", path.display(), e);
+                process::exit(exitcode::NOINPUT)
+            }
+            Error::Scene(e) => {
+                eprintln!("Invalid scene file: {}", e);
+                process::exit(exitcode::DATAERR)
+            }
             Error::ImgWriteIO(e) => {
                 eprintln!("Error while writing rendered image to file: {}", e);
                 process::exit(exitcode::IOERR)
             }
+            Error::ImgWrite(e) => {
+                eprintln!("Unable to encode rendered image: {}", e);
+                process::exit(exitcode::SOFTWARE)
+            }
         }
     }
 }
 
-fn try_main(args: Vec<String>, logger: Logger) -> Result<(), Error> {
-    let Cli { save_path } = Cli::new(&args).ok_or(Error::Cli)?;
-    let scene = scene();
-    let image = rt::Render::new(&scene)
-        .logger(logger)
-        .samples_per_pixel(1000)
-        .diffuse_depth(100)
-        .render();
-    Ok(image.write_png(&save_path)?)
-}
-
-fn scene() -> Scene {
-    let aspect_ratio = 16. / 9.;
-    let width = 600;
-    Scene::new()
-        .width(NonZeroUsize::new(width).unwrap())
-        .height(NonZeroUsize::new((width as f64 / aspect_ratio).round() as usize).unwrap())
-        .cam(Camera {
-            pos: Vector::new(0., 0., 1.),
-            up: NormVector::new(0.3, 1., 1.),
-            to: Vector::new(0., 0., -1.),
-            vfov: Positive::new(60.).unwrap(),
-            aspect_ratio: Positive::new(aspect_ratio).unwrap(),
+fn try_main() -> Result<(), Error> {
+    let cli = Cli::from_iter_safe(std::env::args()).map_err(Error::Cli)?;
+    if cli.adaptive.is_some() && cli.min_samples > cli.samples {
+        return Err(Error::Cli(clap::Error::with_description(
+            "--min-samples should not exceed --samples",
+            clap::ErrorKind::ArgumentConflict,
+        )));
+    }
+    let format = match cli.format {
+        Some(format) => format,
+        None => Format::from_path(&cli.save_path)
+            .ok_or_else(|| Error::UnknownFormat(cli.save_path.clone()))?,
+    };
+    if cli.alpha && !format.has_alpha() {
+        return Err(Error::NoAlpha(format));
+    }
+    let pool = rayon::ThreadPoolBuilder::new()
+        .num_threads(cli.threads.map_or(0, NonZeroUsize::get))
+        .build()
+        .map_err(Error::ThreadPool)?;
+
+    let scene = rt::load_scene(&cli.scene_path)?
+        .resize(cli.width, cli.height)
+        .build();
+    if cli.verbose {
+        println!(
+            "Rendering {} with {} samples per pixel, depth {}, {} threads",
+            cli.scene_path.display(), cli.samples, cli.depth, pool.current_num_threads(),
+        );
+    }
+    // Auxiliary layers do not depend on the number of samples, so passes share them.
+    let aovs = match format {
+        Format::Exr => Some(rt::Render::new(&scene)
+            .sampler(cli.sampler)
+            .seed(cli.seed)
+            .thread_pool(&pool)
+            .render_aovs()),
+        _ => None,
+    };
+    let start = Instant::now();
+    let snapshot_error = Mutex::new(None);
+    let render = rt::Render::new(&scene)
+        .logger(logger(&cli))
+        .thread_pool(&pool)
+        .tile_size(cli.tile_size.get())
+        .tile_order(cli.tile_order)
+        .samples_per_pixel(cli.samples.get())
+        .diffuse_depth(cli.depth)
+        .roulette_depth(Some(cli.roulette_depth).filter(|depth| *depth > 0))
+        .sampler(cli.sampler)
+        .seed(cli.seed)
+        .adaptive(cli.adaptive.map(|threshold| Adaptive {
+            min_samples: cli.min_samples.get(),
+            mean_samples: cli.samples.get(),
+            threshold,
+        }))
+        .progressive(cli.progressive.map(NonZeroUsize::get))
+        // The last pass is saved after rendering, failed snapshots stop it to report the error.
+        .on_pass(Box::new(|sampled, pass, passes| {
+            if pass + 1 == passes {
+                return true;
+            }
+            match save(&cli, format, sampled, aovs.as_ref()) {
+                Ok(()) => true,
+                Err(e) => {
+                    *snapshot_error.lock().unwrap() = Some(e);
+                    false
+                }
+            }
+        }));
+    let sampled = render.render_sampled(cli.alpha);
+    drop(render);
+    if let Some(e) = snapshot_error.into_inner().unwrap() {
+        return Err(e);
+    }
+    if let Some(path) = &cli.sample_heatmap {
+        heatmap(&sampled.samples, sampled.image.w()).write_png(path, None)?;
+    }
+    save(&cli, format, &sampled, aovs.as_ref())?;
+    if cli.verbose {
+        println!("\nFinished in {:.1}s", start.elapsed().as_secs_f64());
+    } else if !cli.quiet {
+        println!("Finished!");
+    }
+    Ok(())
+}
+
+fn save(cli: &Cli, format: Format, sampled: &Sampled, aovs: Option<&Aovs>) -> Result<(), Error> {
+    let hdr = &sampled.image;
+    let alpha = sampled.alpha.as_deref();
+    match format {
+        Format::Png | Format::Ppm | Format::Pam => {
+            // Integer formats store colors not premultiplied by alpha.
+            let straight = alpha.map(|alpha| hdr.unpremultiplied(alpha));
+            let hdr = straight
+                .as_ref()
+                .unwrap_or(hdr)
+                .tone_mapped(cli.tone_map, cli.exposure);
+            if cli.bit_depth == 16 {
+                write_ldr(format, &hdr.to_ldr16(cli.gamma), &cli.save_path, alpha)?
+            } else {
+                write_ldr(format, &hdr.to_ldr(cli.gamma), &cli.save_path, alpha)?
+            }
+        }
+        Format::Exr => {
+            let aovs = aovs.unwrap();
+            let mut writer = ExrWriter::new(hdr.w(), hdr.h())
+                .compression(cli.exr_compression)
+                .pixel_type(if cli.exr_float { ExrPixelType::Float } else { ExrPixelType::Half })
+                .rgb_layer("", hdr)
+                .rgb_layer("albedo", &aovs.albedo)
+                .rgb_layer("normal", &aovs.normal)
+                .channel("depth.Z", aovs.depth.clone());
+            if let Some(alpha) = alpha {
+                writer = writer.channel("A", alpha.to_vec());
+            }
+            writer.write(&cli.save_path)?
+        }
+        Format::Hdr => hdr.write_hdr(&cli.save_path)?,
+        Format::Pfm => hdr.write_pfm(&cli.save_path)?,
+    }
+    Ok(())
+}
+
+/// Sample counts in row-major order colored from blue for the least to red for the most.
+fn heatmap(samples: &[usize], width: usize) -> Image {
+    let min = samples.iter().copied().min().unwrap_or(0);
+    let max = samples.iter().copied().max().unwrap_or(0);
+    let rows: Vec<Vec<_>> = samples
+        .chunks(width)
+        .map(|row| row
+            .iter()
+            .map(|n| {
+                let t = if max > min { (n - min) as f64 / (max - min) as f64 } else { 0. };
+                let channel = |x: f64| (x * u8::MAX as f64).round() as u8;
+                image::Color { r: channel(t), g: channel(1. - (2. * t - 1.).abs()), b: channel(1. - t) }
+            })
+            .collect())
+        .collect();
+    Image::from(rows)
+}
+
+fn write_ldr<T: Sample>(
+    format: Format,
+    image: &Image<T>,
+    path: &Path,
+    alpha: Option<&[f32]>,
+) -> Result<(), image::Error> {
+    match format {
+        Format::Png => image.write_png(path, alpha),
+        Format::Ppm => image.write_ppm(path),
+        Format::Pam => image.write_pam(path, alpha),
+        _ => unreachable!("{:?} is not an integer format", format),
+    }
+}
+
+fn logger(cli: &Cli) -> Logger {
+    let progress = AtomicUsize::new(0);
+    if cli.quiet {
+        Box::new(|_, _| {})
+    } else if cli.verbose {
+        Box::new(move |_, total| {
+            let done = progress.fetch_add(1, Ordering::SeqCst) + 1;
+            print!("\r{:3}%", 100 * done / total);
+            io::stdout().flush().unwrap();
         })
-        .add_sphere(
-            Sphere::new()
-                .center(Vector::new(0., -1., -5.))
-                .radius(Positive::new(2.).unwrap())
-                .lambertian(Lambertian {
-                    albedo: Color { g: 200, b: 255, ..Color::black() }
-                })
-                .build()
-        )
-        .add_sphere(
-            Sphere::new()
-                .center(Vector::new(-3., 1., -5.))
-                .radius(Positive::new(2.).unwrap())
-                .lambertian(Lambertian {
-                    albedo: Color { r: 200, ..Color::black() }
-                })
-                .build()
-        )
-        .add_sphere(
-            Sphere::new()
-                .center(Vector::new(1., 3.5, -6.))
-                .radius(Positive::new(2.).unwrap())
-                .metal(Metal {
-                    albedo: Color { r: 210, g: 100, b: 235 },
-                    fuzz: UniFloat::new(0.1).unwrap(),
-                })
-                .build()
-        )
-        .add_sphere(
-            Sphere::new()
-                .center(Vector::new(5., 0., -6.))
-                .radius(Positive::new(2.).unwrap())
-                .metal(Metal {
-                    albedo: Color { b: 200, r: 200, g: 200 },
-                    fuzz: UniFloat::new(0.2).unwrap(),
-                })
-                .build()
-        )
-        .add_sphere(
-            Sphere::new()
-                .center(Vector::new(0., -101., -5.))
-                .radius(Positive::new(100.).unwrap())
-                .lambertian(Lambertian {
-                    albedo: Color { b: 200, r: 200, ..Color::black() }
-                })
-                .build()
-        )
-        .background_getter(Box::new(|Ray { dir, .. }| {
-            let t = 0.5 * (dir.get() + Vector::new(1., 1., 1.));
-            let r = 255. * (1. - t[0] * 0.5);
-            let g = 255. * (1. - t[1] * 0.7);
-            let b = 255. * (1. - t[2] * 1.0);
-            Color {
-                r: r as u8,
-                g: g as u8,
-                b: b as u8,
+    } else {
+        Box::new(move |_, _| {
+            if progress.fetch_add(1, Ordering::SeqCst).is_multiple_of(10) {
+                print!(".");
+                io::stdout().flush().unwrap();
             }
-        }))
-        .build()
+        })
+    }
 }